@@ -0,0 +1,204 @@
+// SPDX-FileCopyrightText: 2024 Petr Pucil <petr.pucil@seznam.cz>
+//
+// SPDX-License-Identifier: MIT
+
+use std::{collections::{BTreeSet, HashMap, HashSet}, fmt};
+
+use itertools::Itertools;
+
+use crate::Input;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RulesReport {
+    pub contradictions: Vec<(u32, u32)>,
+    pub cycles: Vec<Vec<u32>>,
+    pub redundant_rules: Vec<(u32, u32)>,
+    pub pages_without_rules: Vec<u32>,
+}
+
+pub fn check_rules(input: &Input) -> RulesReport {
+    let rules = &input.pages_expected_after;
+
+    let mut contradictions = vec![];
+    let mut redundant_rules = vec![];
+    for (page_a, page_b) in all_rules(rules) {
+        if page_a < page_b && rules.get(&page_b).is_some_and(|after_b| after_b.contains(&page_a)) {
+            contradictions.push((page_a, page_b));
+        }
+        if is_reachable_without_rule(rules, page_a, page_b) {
+            redundant_rules.push((page_a, page_b));
+        }
+    }
+
+    let cycles = find_cycles(rules);
+
+    let pages_with_rules = all_pages(rules);
+    let pages_without_rules = input.updates
+        .iter()
+        .flatten()
+        .copied()
+        .filter(|page| !pages_with_rules.contains(page))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    RulesReport { contradictions, cycles, redundant_rules, pages_without_rules }
+}
+
+impl fmt::Display for RulesReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "contradictory rules: {}", self.contradictions.len())?;
+        for (page_a, page_b) in &self.contradictions {
+            writeln!(f, "  {page_a}|{page_b} and {page_b}|{page_a}")?;
+        }
+        writeln!(f, "cycles: {}", self.cycles.len())?;
+        for cycle in &self.cycles {
+            writeln!(f, "  {} pages: {}", cycle.len(), cycle.iter().join(", "))?;
+        }
+        writeln!(f, "redundant rules (implied by transitivity): {}", self.redundant_rules.len())?;
+        for (page_a, page_b) in &self.redundant_rules {
+            writeln!(f, "  {page_a}|{page_b}")?;
+        }
+        writeln!(f, "pages in updates without any rule: {}", self.pages_without_rules.len())?;
+        if !self.pages_without_rules.is_empty() {
+            writeln!(f, "  {}", self.pages_without_rules.iter().join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+// Groups of pages that are all mutually reachable via the rules
+pub(crate) fn find_cycles(rules: &HashMap<u32, HashSet<u32>>) -> Vec<Vec<u32>> {
+    strongly_connected_components(rules)
+        .into_iter()
+        .filter(|component| {
+            // A single page only forms a cycle if there is a rule `X|X`
+            component.len() > 1 || rules.get(&component[0]).is_some_and(|after| after.contains(&component[0]))
+        })
+        .sorted()
+        .collect()
+}
+
+pub(crate) fn all_rules(rules: &HashMap<u32, HashSet<u32>>) -> impl Iterator<Item = (u32, u32)> + use<'_> {
+    rules
+        .iter()
+        .flat_map(|(&page_a, after_a)| after_a.iter().map(move |&page_b| (page_a, page_b)))
+        .sorted()
+}
+
+pub(crate) fn all_pages(rules: &HashMap<u32, HashSet<u32>>) -> BTreeSet<u32> {
+    rules
+        .iter()
+        .flat_map(|(&page_a, after_a)| std::iter::once(page_a).chain(after_a.iter().copied()))
+        .collect()
+}
+
+// Whether `page_b` is still reachable from `page_a` after removing the direct rule `page_a|page_b`
+pub(crate) fn is_reachable_without_rule(rules: &HashMap<u32, HashSet<u32>>, page_a: u32, page_b: u32) -> bool {
+    let mut visited = HashSet::from([page_a]);
+    let mut stack: Vec<u32> = rules[&page_a]
+        .iter()
+        .copied()
+        .filter(|&page| page != page_b)
+        .collect();
+    while let Some(page) = stack.pop() {
+        if page == page_b {
+            return true;
+        }
+        if !visited.insert(page) {
+            continue;
+        }
+        if let Some(after) = rules.get(&page) {
+            stack.extend(after.iter().copied().filter(|p| !visited.contains(p)));
+        }
+    }
+    false
+}
+
+// Tarjan's algorithm: https://en.wikipedia.org/wiki/Tarjan%27s_strongly_connected_components_algorithm
+fn strongly_connected_components(rules: &HashMap<u32, HashSet<u32>>) -> Vec<Vec<u32>> {
+    struct State<'a> {
+        rules: &'a HashMap<u32, HashSet<u32>>,
+        next_index: usize,
+        indices: HashMap<u32, usize>,
+        low_links: HashMap<u32, usize>,
+        stack: Vec<u32>,
+        on_stack: HashSet<u32>,
+        components: Vec<Vec<u32>>,
+    }
+
+    fn visit(state: &mut State, page: u32) {
+        state.indices.insert(page, state.next_index);
+        state.low_links.insert(page, state.next_index);
+        state.next_index += 1;
+        state.stack.push(page);
+        state.on_stack.insert(page);
+
+        for &next in state.rules.get(&page).into_iter().flatten() {
+            if !state.indices.contains_key(&next) {
+                visit(state, next);
+                let low_link = state.low_links[&page].min(state.low_links[&next]);
+                state.low_links.insert(page, low_link);
+            } else if state.on_stack.contains(&next) {
+                let low_link = state.low_links[&page].min(state.indices[&next]);
+                state.low_links.insert(page, low_link);
+            }
+        }
+
+        if state.low_links[&page] == state.indices[&page] {
+            let mut component = vec![];
+            loop {
+                let member = state.stack.pop().unwrap();
+                state.on_stack.remove(&member);
+                component.push(member);
+                if member == page {
+                    break;
+                }
+            }
+            component.sort();
+            state.components.push(component);
+        }
+    }
+
+    let mut state = State {
+        rules,
+        next_index: 0,
+        indices: HashMap::new(),
+        low_links: HashMap::new(),
+        stack: vec![],
+        on_stack: HashSet::new(),
+        components: vec![],
+    };
+    for page in all_pages(rules) {
+        if !state.indices.contains_key(&page) {
+            visit(&mut state, page);
+        }
+    }
+    state.components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::rules;
+
+    #[test]
+    fn consistent_rules() {
+        let input = Input { pages_expected_after: rules(&[(1, 2), (2, 3)]), updates: vec![vec![1, 2, 3]] };
+        assert_eq!(check_rules(&input), RulesReport::default());
+    }
+
+    #[test]
+    fn inconsistent_rules() {
+        let input = Input {
+            pages_expected_after: rules(&[(1, 2), (2, 1), (3, 4), (4, 5), (5, 3), (6, 7), (7, 8), (6, 8)]),
+            updates: vec![vec![1, 2, 9], vec![6, 10, 9]],
+        };
+        assert_eq!(check_rules(&input), RulesReport {
+            contradictions: vec![(1, 2)],
+            cycles: vec![vec![1, 2], vec![3, 4, 5]],
+            redundant_rules: vec![(6, 8)],
+            pages_without_rules: vec![9, 10],
+        });
+    }
+}
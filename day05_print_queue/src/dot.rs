@@ -0,0 +1,134 @@
+// SPDX-FileCopyrightText: 2024 Petr Pucil <petr.pucil@seznam.cz>
+//
+// SPDX-License-Identifier: MIT
+
+use std::{collections::{BTreeSet, HashMap, HashSet}, io::{self, Write}};
+
+use crate::check_rules::{all_pages, all_rules, find_cycles, is_reachable_without_rule};
+
+// Writes the rules as a GraphViz digraph with an edge `A -> B` for each rule `A|B`. If `pages`
+// are given, only the rules between them are included.
+pub fn write_dot<W: Write>(
+    mut wtr: W,
+    input_pages_expected_after: &HashMap<u32, HashSet<u32>>,
+    pages: Option<&[u32]>,
+    transitive_reduction: bool,
+) -> io::Result<()> {
+    let rules = match pages {
+        Some(pages) => restrict_rules(input_pages_expected_after, pages),
+        None => input_pages_expected_after.clone(),
+    };
+    let nodes: BTreeSet<u32> = match pages {
+        // Pages without any rules between them are still shown as isolated nodes
+        Some(pages) => pages.iter().copied().collect(),
+        None => all_pages(&rules),
+    };
+
+    let mut edges: Vec<(u32, u32)> = all_rules(&rules).collect();
+    if transitive_reduction {
+        // Removing every rule implied by the others would break cycles, so the rules within a cycle
+        // are all kept and only the rules between cycles are reduced, as if each cycle was a
+        // single page represented by its lowest page
+        let representatives: HashMap<u32, u32> = find_cycles(&rules)
+            .iter()
+            .flat_map(|cycle| {
+                let representative = *cycle.iter().min().unwrap();
+                cycle.iter().map(move |&page| (page, representative))
+            })
+            .collect();
+        let representative = |page| representatives.get(&page).copied().unwrap_or(page);
+        let mut condensed_rules: HashMap<u32, HashSet<u32>> = HashMap::new();
+        for &(page_a, page_b) in &edges {
+            let (repr_a, repr_b) = (representative(page_a), representative(page_b));
+            if repr_a != repr_b {
+                condensed_rules.entry(repr_a).or_default().insert(repr_b);
+            }
+        }
+        edges.retain(|&(page_a, page_b)| {
+            let (repr_a, repr_b) = (representative(page_a), representative(page_b));
+            repr_a == repr_b || !is_reachable_without_rule(&condensed_rules, repr_a, repr_b)
+        });
+    }
+
+    writeln!(wtr, "digraph rules {{")?;
+    for page in nodes {
+        writeln!(wtr, "    {page};")?;
+    }
+    for (page_a, page_b) in edges {
+        writeln!(wtr, "    {page_a} -> {page_b};")?;
+    }
+    writeln!(wtr, "}}")?;
+    Ok(())
+}
+
+fn restrict_rules(input_pages_expected_after: &HashMap<u32, HashSet<u32>>, pages: &[u32]) -> HashMap<u32, HashSet<u32>> {
+    let pages: HashSet<u32> = pages.iter().copied().collect();
+    input_pages_expected_after
+        .iter()
+        .filter(|(page_a, _)| pages.contains(page_a))
+        .map(|(&page_a, after_a)| (page_a, after_a.intersection(&pages).copied().collect::<HashSet<_>>()))
+        .filter(|(_, after_a)| !after_a.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::rules;
+
+    #[test]
+    fn restricted_and_reduced() {
+        let rules = rules(&[(1, 2), (1, 3), (1, 5), (2, 3)]);
+
+        let mut dot = vec![];
+        write_dot(&mut dot, &rules, Some(&[3, 1, 2, 4]), true).unwrap();
+        assert_eq!(String::from_utf8(dot).unwrap(), "\
+digraph rules {
+    1;
+    2;
+    3;
+    4;
+    1 -> 2;
+    2 -> 3;
+}
+");
+
+        let mut dot = vec![];
+        write_dot(&mut dot, &rules, None, false).unwrap();
+        assert_eq!(String::from_utf8(dot).unwrap(), "\
+digraph rules {
+    1;
+    2;
+    3;
+    5;
+    1 -> 2;
+    1 -> 3;
+    1 -> 5;
+    2 -> 3;
+}
+");
+    }
+
+    #[test]
+    fn reduced_with_cycles() {
+        // 1, 2 and 3 form a cycle, `1|5` is implied by `3|4` and `4|5`
+        let rules = rules(&[(1, 2), (1, 5), (2, 3), (3, 1), (3, 4), (4, 5)]);
+
+        let mut dot = vec![];
+        write_dot(&mut dot, &rules, None, true).unwrap();
+        assert_eq!(String::from_utf8(dot).unwrap(), "\
+digraph rules {
+    1;
+    2;
+    3;
+    4;
+    5;
+    1 -> 2;
+    2 -> 3;
+    3 -> 1;
+    3 -> 4;
+    4 -> 5;
+}
+");
+    }
+}
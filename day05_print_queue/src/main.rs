@@ -2,13 +2,52 @@
 //
 // SPDX-License-Identifier: MIT
 
-use std::{cmp::Ordering, collections::{HashMap, HashSet}, io};
+use std::{cmp::Ordering, collections::{HashMap, HashSet}, env, io::{self, BufRead}};
+
+use itertools::Itertools;
+
+mod check_rules;
+mod dot;
+mod orderings;
 
 fn main() {
-    let input = read_input_from_stdin();
-    // let res = solve_part1(&input);
-    let res = solve_part2(input);
-    println!("{res}");
+    let mut args = env::args().skip(1);
+    let mode = args.next();
+    let input = read_input(io::stdin().lock());
+    match mode.as_deref() {
+        None => {
+            // let res = solve_part1(&input);
+            let res = solve_part2(input);
+            println!("{res}");
+        }
+        Some("check-rules") => {
+            let report = check_rules::check_rules(&input);
+            print!("{report}");
+        }
+        Some("explain") => print_violation_explanations(&input),
+        Some("count-orderings") => print_ordering_counts(&input),
+        Some("dot") => {
+            // dot [--update <update number>] [--transitive-reduction]
+            let mut pages = None;
+            let mut transitive_reduction = false;
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--update" => {
+                        let update_no: usize = args.next().expect("missing update number").parse().unwrap();
+                        let num_updates = input.updates.len();
+                        if !(1..=num_updates).contains(&update_no) {
+                            panic!("update number {update_no} is out of range, expected 1 to {num_updates}");
+                        }
+                        pages = Some(input.updates[update_no - 1].as_slice());
+                    }
+                    "--transitive-reduction" => transitive_reduction = true,
+                    _ => panic!("unknown option {arg:?}"),
+                }
+            }
+            dot::write_dot(io::stdout().lock(), &input.pages_expected_after, pages, transitive_reduction).unwrap();
+        }
+        Some(mode) => panic!("unknown mode {mode:?}, expected \"check-rules\", \"explain\", \"count-orderings\" or \"dot\""),
+    }
 }
 
 fn solve_part1(input: &Input) -> u32 {
@@ -36,18 +75,32 @@ fn middle_page_number(update: &[u32]) -> u32 {
 }
 
 fn is_update_correct(update: &[u32], input_pages_expected_after: &HashMap<u32, HashSet<u32>>) -> bool {
-    for (i, page) in update.iter().enumerate() {
+    find_rule_violations(update, input_pages_expected_after).is_empty()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct RuleViolation {
+    // Rule `page|prev_page` is violated, because `prev_page` appears before `page` in the update
+    page_index: usize,
+    page: u32,
+    prev_page_index: usize,
+    prev_page: u32,
+}
+
+fn find_rule_violations(update: &[u32], input_pages_expected_after: &HashMap<u32, HashSet<u32>>) -> Vec<RuleViolation> {
+    let mut violations = vec![];
+    for (i, &page) in update.iter().enumerate() {
         let pages_actually_before = &update[0..i];
         let Some(pages_expected_after) = input_pages_expected_after.get(&page) else {
             continue;
         };
-        for &prev_page in pages_actually_before {
+        for (j, &prev_page) in pages_actually_before.iter().enumerate() {
             if pages_expected_after.contains(&prev_page) {
-                return false;
+                violations.push(RuleViolation { page_index: i, page, prev_page_index: j, prev_page });
             }
         }
     }
-    true
+    violations
 }
 
 fn fix_update(update: &mut [u32], input_pages_expected_after: &HashMap<u32, HashSet<u32>>) {
@@ -66,31 +119,360 @@ fn fix_update(update: &mut [u32], input_pages_expected_after: &HashMap<u32, Hash
     });
 }
 
+#[derive(Debug, PartialEq, Eq)]
+struct PageMove {
+    page: u32,
+    from_index: usize,
+    // `None` means moving the page to the front
+    after_page: Option<u32>,
+}
+
+// The fewest moves that fix `update`, meant to be applied one after another, or `None` if the
+// rules between its pages are cyclic. The pages that stay in place are the largest subsequence of
+// `update` which violates no rule (including the rules implied by the others).
+fn find_fixing_moves(update: &[u32], input_pages_expected_after: &HashMap<u32, HashSet<u32>>) -> Option<Vec<PageMove>> {
+    let must_precede = orderings::precedence_closure(update, input_pages_expected_after)?;
+    let fixed_update: Vec<u32> = ordering_keeping(&must_precede, &find_largest_valid_subsequence(&must_precede))
+        .into_iter()
+        .map(|i| update[i])
+        .collect();
+    let fixed_indices: Vec<usize> = update
+        .iter()
+        .map(|page| fixed_update.iter().position(|p| p == page).unwrap())
+        .collect();
+
+    // Longest increasing subsequence of `fixed_indices`, O(n^2) is fine for the update lengths
+    let mut lis_len = vec![1; update.len()];
+    let mut lis_prev = vec![None; update.len()];
+    for i in 0..update.len() {
+        for j in 0..i {
+            if fixed_indices[j] < fixed_indices[i] && lis_len[j] + 1 > lis_len[i] {
+                lis_len[i] = lis_len[j] + 1;
+                lis_prev[i] = Some(j);
+            }
+        }
+    }
+    let mut is_kept = vec![false; update.len()];
+    let mut lis_end = (0..update.len()).max_by_key(|&i| lis_len[i]);
+    while let Some(i) = lis_end {
+        is_kept[i] = true;
+        lis_end = lis_prev[i];
+    }
+
+    // Placing the moved pages in their fixed order guarantees that the page they are placed
+    // after is already where it belongs
+    let moves = (0..update.len())
+        .filter(|&i| !is_kept[i])
+        .sorted_by_key(|&i| fixed_indices[i])
+        .map(|i| PageMove {
+            page: update[i],
+            from_index: i,
+            after_page: fixed_indices[i].checked_sub(1).map(|j| fixed_update[j]),
+        })
+        .collect();
+    Some(moves)
+}
+
+// Pages `i < j` violate a rule iff `must_precede[j][i]`, which makes the violations a partial
+// order. The largest subsequence without violations is its maximum antichain, which is found from
+// a maximum bipartite matching via Dilworth's and Kőnig's theorems.
+fn find_largest_valid_subsequence(must_precede: &[Vec<bool>]) -> Vec<bool> {
+    let n = must_precede.len();
+    let violates = |i: usize, j: usize| i < j && must_precede[j][i];
+
+    // Kuhn's algorithm, `matched_left[j]` is the page matched to `j` on the right side
+    fn try_augment(i: usize, violates: &dyn Fn(usize, usize) -> bool, visited: &mut [bool], matched_left: &mut [Option<usize>]) -> bool {
+        for j in 0..visited.len() {
+            if violates(i, j) && !visited[j] {
+                visited[j] = true;
+                if matched_left[j].is_none_or(|k| try_augment(k, violates, visited, matched_left)) {
+                    matched_left[j] = Some(i);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+    let mut matched_left = vec![None; n];
+    for i in 0..n {
+        try_augment(i, &violates, &mut vec![false; n], &mut matched_left);
+    }
+    let mut matched_right = vec![None; n];
+    for (j, &i) in matched_left.iter().enumerate() {
+        if let Some(i) = i {
+            matched_right[i] = Some(j);
+        }
+    }
+
+    // The left pages reachable from the unmatched left pages by alternating paths, and the right
+    // pages on them. The minimum vertex cover consists of the unreached left pages and the reached
+    // right pages, the pages with neither side in the cover form the antichain.
+    let mut reached_left: Vec<bool> = matched_right.iter().map(Option::is_none).collect();
+    let mut reached_right = vec![false; n];
+    let mut stack: Vec<usize> = (0..n).filter(|&i| reached_left[i]).collect();
+    while let Some(i) = stack.pop() {
+        for j in 0..n {
+            if !violates(i, j) || reached_right[j] {
+                continue;
+            }
+            reached_right[j] = true;
+            if let Some(k) = matched_left[j] {
+                if !reached_left[k] {
+                    reached_left[k] = true;
+                    stack.push(k);
+                }
+            }
+        }
+    }
+    (0..n).map(|i| reached_left[i] && !reached_right[i]).collect()
+}
+
+// A valid ordering (as indices into the update) that keeps the `is_kept` pages in their order,
+// which exists if they violate no rule between them, taking the earliest page when there is a choice
+fn ordering_keeping(must_precede: &[Vec<bool>], is_kept: &[bool]) -> Vec<usize> {
+    let n = must_precede.len();
+    let mut is_placed = vec![false; n];
+    let mut ordering = Vec::with_capacity(n);
+    while ordering.len() < n {
+        let j = (0..n)
+            .find(|&j| {
+                !is_placed[j]
+                    && (0..n).all(|i| !must_precede[i][j] || is_placed[i])
+                    && (!is_kept[j] || (0..j).all(|i| !is_kept[i] || is_placed[i]))
+            })
+            .unwrap();
+        is_placed[j] = true;
+        ordering.push(j);
+    }
+    ordering
+}
+
+fn print_violation_explanations(input: &Input) {
+    for (update_no, update) in (1_usize..).zip(&input.updates) {
+        let violations = find_rule_violations(update, &input.pages_expected_after);
+        if violations.is_empty() {
+            continue;
+        }
+        println!("update {update_no} ({}):", update.iter().join(","));
+        println!("  violations ({}):", violations.len());
+        for v in &violations {
+            println!(
+                "    {} at index {} appears before {} at index {}, but rule {}|{} exists",
+                v.prev_page, v.prev_page_index, v.page, v.page_index, v.page, v.prev_page,
+            );
+        }
+        let Some(moves) = find_fixing_moves(update, &input.pages_expected_after) else {
+            println!("  no valid order, the rules between its pages are cyclic");
+            continue;
+        };
+        println!("  moves ({}):", moves.len());
+        for m in &moves {
+            match m.after_page {
+                Some(after_page) => println!("    move {} (index {}) after {after_page}", m.page, m.from_index),
+                None => println!("    move {} (index {}) to the front", m.page, m.from_index),
+            }
+        }
+    }
+}
+
+fn print_ordering_counts(input: &Input) {
+    for (update_no, update) in (1_usize..).zip(&input.updates) {
+        let count = orderings::count_orderings(update, &input.pages_expected_after);
+        match orderings::fixed_middle_page(update, &input.pages_expected_after) {
+            Some(middle_page) => println!("update {update_no}: {count}, middle page {middle_page}"),
+            None => println!("update {update_no}: {count}, middle page ambiguous"),
+        }
+    }
+}
+
 #[derive(Debug)]
 struct Input {
     pages_expected_after: HashMap<u32, HashSet<u32>>,
     updates: Vec<Vec<u32>>,
 }
 
-fn read_input_from_stdin() -> Input {
+// Rules and updates are separated by the first blank line. Trailing whitespace (including the
+// `\r` of CRLF line endings) is ignored, as well as blank lines after the updates.
+fn read_input<R: BufRead>(rdr: R) -> Input {
     let mut pages_expected_after: HashMap<u32, HashSet<u32>> = HashMap::new();
-    for line in io::stdin().lines() {
+    let mut updates = vec![];
+    let mut is_in_rules_section = true;
+    for (line_no, line) in (1_usize..).zip(rdr.lines()) {
         let line = line.unwrap();
-        if line.is_empty() {
-            break;
+        let line = line.trim_end();
+        if is_in_rules_section {
+            if line.is_empty() {
+                is_in_rules_section = false;
+                continue;
+            }
+            let (page_a, page_b) = line
+                .split_once('|')
+                .unwrap_or_else(|| panic!("line {line_no}: expected rule \"X|Y\", but got {line:?}"));
+            let page_a = page_a.parse::<u32>().unwrap();
+            let page_b = page_b.parse::<u32>().unwrap();
+            let after_set = pages_expected_after.entry(page_a).or_default();
+            after_set.insert(page_b);
+        } else {
+            if line.is_empty() {
+                continue;
+            }
+            updates.push(line.split(',').map(|x| x.parse::<u32>().unwrap()).collect());
         }
-        let (page_a, page_b) = line.split_once('|').unwrap();
-        let page_a = page_a.parse::<u32>().unwrap();
-        let page_b = page_b.parse::<u32>().unwrap();
-        let after_set = pages_expected_after.entry(page_a).or_default();
-        after_set.insert(page_b);
     }
 
-    let mut updates = vec![];
-    for line in io::stdin().lines() {
-        let line = line.unwrap();
-        updates.push(line.split(',').map(|x| x.parse::<u32>().unwrap()).collect());
+    Input { pages_expected_after, updates }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `page_a|page_b` rules given as pairs, shared by the tests of all modules
+    pub(crate) fn rules(pairs: &[(u32, u32)]) -> HashMap<u32, HashSet<u32>> {
+        let mut res: HashMap<u32, HashSet<u32>> = HashMap::new();
+        for &(page_a, page_b) in pairs {
+            res.entry(page_a).or_default().insert(page_b);
+        }
+        res
     }
 
-    Input { pages_expected_after: pages_expected_after, updates }
+    const EXAMPLE: &str = "\
+47|53
+97|13
+97|61
+97|47
+75|29
+61|13
+75|53
+29|13
+97|29
+53|29
+61|53
+97|53
+61|29
+47|13
+75|47
+97|75
+47|61
+75|61
+47|29
+75|13
+53|13
+
+75,47,61,53,29
+97,61,53,29,13
+75,29,13
+75,97,47,61,53
+61,13,29
+97,13,75,29,47
+";
+
+    #[test]
+    fn example() {
+        let input = read_input(EXAMPLE.as_bytes());
+        assert_eq!(input.pages_expected_after.values().map(|s| s.len()).sum::<usize>(), 21);
+        assert_eq!(input.updates.len(), 6);
+        assert_eq!(solve_part1(&input), 143);
+        assert_eq!(solve_part2(input), 123);
+    }
+
+    #[test]
+    fn crlf_and_trailing_whitespace() {
+        let example_crlf = EXAMPLE.replace('\n', " \r\n") + "\r\n";
+        let input = read_input(example_crlf.as_bytes());
+        assert_eq!(input.updates.len(), 6);
+        assert_eq!(solve_part1(&input), 143);
+        assert_eq!(solve_part2(input), 123);
+    }
+
+    #[test]
+    fn fixing_moves() {
+        let pages_expected_after = rules(&[
+            (97, 13), (97, 29), (97, 47), (97, 75),
+            (75, 13), (75, 29), (75, 47),
+            (47, 13), (47, 29),
+            (29, 13),
+        ]);
+        let update = [97, 13, 75, 29, 47];
+        assert_eq!(find_rule_violations(&update, &pages_expected_after).len(), 4);
+        assert_eq!(find_fixing_moves(&update, &pages_expected_after), Some(vec![
+            PageMove { page: 29, from_index: 3, after_page: Some(47) },
+            PageMove { page: 13, from_index: 1, after_page: Some(29) },
+        ]));
+
+        let update = [75, 97, 47, 29];
+        assert_eq!(find_fixing_moves(&update, &pages_expected_after), Some(vec![
+            PageMove { page: 97, from_index: 1, after_page: None },
+        ]));
+        assert_eq!(find_fixing_moves(&[97, 75, 13], &pages_expected_after), Some(vec![]));
+    }
+
+    fn apply_moves(update: &[u32], moves: &[PageMove]) -> Vec<u32> {
+        let mut update = update.to_vec();
+        for m in moves {
+            update.retain(|&page| page != m.page);
+            let index = m.after_page.map_or(0, |after_page| update.iter().position(|&p| p == after_page).unwrap() + 1);
+            update.insert(index, m.page);
+        }
+        update
+    }
+
+    #[test]
+    fn fixing_moves_partial_rules() {
+        // The rules leave page 15 unordered and don't relate 13 and 14 to 11 and 12
+        let pages_expected_after = rules(&[(13, 14), (10, 13), (10, 11), (11, 12)]);
+        let update = [15, 14, 13, 12, 11, 10];
+        let moves = find_fixing_moves(&update, &pages_expected_after).unwrap();
+        assert_eq!(moves.len(), 3);
+        let fixed_update = apply_moves(&update, &moves);
+        assert_eq!(fixed_update, [15, 10, 13, 14, 11, 12]);
+        assert!(is_update_correct(&fixed_update, &pages_expected_after));
+
+        // Only 15 needs to move, the unrelated page 13 stays
+        let pages_expected_after = rules(&[(15, 12), (15, 10), (15, 14), (12, 14), (10, 11)]);
+        let update = [10, 11, 12, 13, 14, 15];
+        assert_eq!(find_fixing_moves(&update, &pages_expected_after), Some(vec![
+            PageMove { page: 15, from_index: 5, after_page: None },
+        ]));
+
+        let cycle = rules(&[(1, 2), (1, 3), (2, 3), (3, 1)]);
+        assert_eq!(find_fixing_moves(&[1, 2, 3], &cycle), None);
+    }
+
+    #[test]
+    fn fixing_moves_are_minimal() {
+        // Compared with the longest subsequence without violations found by brute force
+        let mut seed = 1_u64;
+        let mut next_random = |n: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) % n
+        };
+        for _ in 0..300 {
+            let pages: Vec<u32> = (1..=7).collect();
+            // Rules only from lower to higher pages, so that they are acyclic
+            let pairs: Vec<(u32, u32)> = pages
+                .iter()
+                .copied()
+                .tuple_combinations()
+                .filter(|_| next_random(4) == 0)
+                .collect();
+            let pages_expected_after = rules(&pairs);
+            let mut update = pages.clone();
+            for i in (1..update.len()).rev() {
+                update.swap(i, next_random(i as u64 + 1) as usize);
+            }
+            let moves = find_fixing_moves(&update, &pages_expected_after).unwrap();
+            assert!(is_update_correct(&apply_moves(&update, &moves), &pages_expected_after), "{update:?}");
+            let must_precede = orderings::precedence_closure(&update, &pages_expected_after).unwrap();
+            let max_kept = (0_u32..1 << update.len())
+                .filter(|mask| {
+                    let kept: Vec<usize> = (0..update.len()).filter(|i| mask & 1 << i != 0).collect();
+                    kept.iter().tuple_combinations().all(|(&i, &j)| !must_precede[j][i])
+                })
+                .map(|mask| mask.count_ones() as usize)
+                .max()
+                .unwrap();
+            assert_eq!(moves.len(), update.len() - max_kept, "{update:?}");
+        }
+    }
 }
@@ -0,0 +1,210 @@
+// SPDX-FileCopyrightText: 2024 Petr Pucil <petr.pucil@seznam.cz>
+//
+// SPDX-License-Identifier: MIT
+
+use std::{collections::{HashMap, HashSet}, fmt};
+
+// The exact count is a DP over all subsets of the update pages, i.e. O(2^n * n) time and memory
+const MAX_EXACT_PAGES: usize = 20;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum OrderingCount {
+    Exact(u128),
+    Bounds { lower: u128, upper: u128 },
+}
+
+impl fmt::Display for OrderingCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderingCount::Exact(1) => write!(f, "1 ordering"),
+            OrderingCount::Exact(count) => write!(f, "{count} orderings"),
+            OrderingCount::Bounds { lower, upper } => write!(f, "between {lower} and {upper} orderings"),
+        }
+    }
+}
+
+// Number of orderings of `update` that satisfy all rules between its pages (the linear
+// extensions of the partial order given by the rules)
+pub fn count_orderings(update: &[u32], input_pages_expected_after: &HashMap<u32, HashSet<u32>>) -> OrderingCount {
+    let Some(must_precede) = precedence_closure(update, input_pages_expected_after) else {
+        return OrderingCount::Exact(0);
+    };
+    if update.len() <= MAX_EXACT_PAGES {
+        OrderingCount::Exact(count_orderings_exact(&must_precede))
+    } else {
+        OrderingCount::Bounds {
+            lower: count_orderings_lower_bound(&must_precede),
+            upper: count_orderings_upper_bound(&must_precede),
+        }
+    }
+}
+
+// The middle page if it is the same in all valid orderings of `update`, which is the case iff
+// some page must be preceded by exactly half of the other pages and followed by the rest
+pub fn fixed_middle_page(update: &[u32], input_pages_expected_after: &HashMap<u32, HashSet<u32>>) -> Option<u32> {
+    let must_precede = precedence_closure(update, input_pages_expected_after)?;
+    let n = update.len();
+    let mid = n / 2;
+    (0..n)
+        .find(|&j| {
+            let num_before = (0..n).filter(|&i| must_precede[i][j]).count();
+            let num_after = (0..n).filter(|&k| must_precede[j][k]).count();
+            num_before == mid && num_after == n - 1 - mid
+        })
+        .map(|j| update[j])
+}
+
+// `must_precede[i][j]` says whether `update[i]` must come before `update[j]`, considering only
+// the rules between pages of the update. Returns `None` if these rules contain a cycle.
+pub(crate) fn precedence_closure(update: &[u32], input_pages_expected_after: &HashMap<u32, HashSet<u32>>) -> Option<Vec<Vec<bool>>> {
+    let n = update.len();
+    let mut must_precede = vec![vec![false; n]; n];
+    for (i, page) in update.iter().enumerate() {
+        let Some(pages_expected_after) = input_pages_expected_after.get(page) else {
+            continue;
+        };
+        for (j, other_page) in update.iter().enumerate() {
+            if pages_expected_after.contains(other_page) {
+                must_precede[i][j] = true;
+            }
+        }
+    }
+    // Floyd-Warshall
+    for k in 0..n {
+        let after_k = must_precede[k].clone();
+        for row in must_precede.iter_mut().filter(|row| row[k]) {
+            for (is_after, &is_after_k) in row.iter_mut().zip(&after_k) {
+                *is_after |= is_after_k;
+            }
+        }
+    }
+    if (0..n).any(|i| must_precede[i][i]) {
+        return None;
+    }
+    Some(must_precede)
+}
+
+fn count_orderings_exact(must_precede: &[Vec<bool>]) -> u128 {
+    let n = must_precede.len();
+    let required_masks: Vec<usize> = (0..n)
+        .map(|j| (0..n).filter(|&i| must_precede[i][j]).fold(0, |mask, i| mask | 1 << i))
+        .collect();
+    // `counts[mask]` is the number of valid orderings of the pages in `mask` placed first
+    let mut counts = vec![0_u128; 1 << n];
+    counts[0] = 1;
+    for mask in 0..counts.len() {
+        let count = counts[mask];
+        if count == 0 {
+            continue;
+        }
+        for (j, &required_mask) in required_masks.iter().enumerate() {
+            if mask & (1 << j) == 0 && mask & required_mask == required_mask {
+                counts[mask | 1 << j] += count;
+            }
+        }
+    }
+    counts[counts.len() - 1]
+}
+
+// Pages with a strictly smaller set of required predecessors come first
+fn topological_order(must_precede: &[Vec<bool>]) -> Vec<usize> {
+    let n = must_precede.len();
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by_key(|&j| (0..n).filter(|&i| must_precede[i][j]).count());
+    order
+}
+
+// Ordering the pages level by level (by the length of the longest chain of pages that must
+// precede them) and each level arbitrarily always gives a valid ordering
+fn count_orderings_lower_bound(must_precede: &[Vec<bool>]) -> u128 {
+    let n = must_precede.len();
+    let mut levels = vec![0; n];
+    for j in topological_order(must_precede) {
+        levels[j] = (0..n)
+            .filter(|&i| must_precede[i][j])
+            .map(|i| levels[i] + 1)
+            .max()
+            .unwrap_or(0);
+    }
+    let mut level_sizes: HashMap<usize, u128> = HashMap::new();
+    for level in levels {
+        *level_sizes.entry(level).or_default() += 1;
+    }
+    level_sizes
+        .values()
+        .map(|&size| (1..=size).fold(1_u128, |acc, k| acc.saturating_mul(k)))
+        .fold(1, |acc, factorial| acc.saturating_mul(factorial))
+}
+
+// Once the pages are split into chains, a valid ordering is determined by how the chains are
+// interleaved, so the number of interleavings is an upper bound
+fn count_orderings_upper_bound(must_precede: &[Vec<bool>]) -> u128 {
+    let mut chains: Vec<Vec<usize>> = vec![];
+    for j in topological_order(must_precede) {
+        match chains.iter_mut().find(|chain| must_precede[*chain.last().unwrap()][j]) {
+            Some(chain) => chain.push(j),
+            None => chains.push(vec![j]),
+        }
+    }
+    let mut num_placed = 0;
+    let mut res: u128 = 1;
+    for chain in chains {
+        num_placed += chain.len();
+        res = res.saturating_mul(binomial(num_placed as u128, chain.len() as u128));
+    }
+    res
+}
+
+fn binomial(n: u128, k: u128) -> u128 {
+    let mut res: u128 = 1;
+    for i in 0..k {
+        // `res * (n - i)` is always divisible by `i + 1`
+        let Some(product) = res.checked_mul(n - i) else {
+            return u128::MAX;
+        };
+        res = product / (i + 1);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::rules;
+
+    #[test]
+    fn exact_count() {
+        let chain = rules(&[(1, 2), (2, 3)]);
+        assert_eq!(count_orderings(&[3, 1, 2], &chain), OrderingCount::Exact(1));
+        assert_eq!(fixed_middle_page(&[3, 1, 2], &chain), Some(2));
+
+        let fork = rules(&[(1, 2), (1, 3)]);
+        assert_eq!(count_orderings(&[1, 2, 3], &fork), OrderingCount::Exact(2));
+        assert_eq!(fixed_middle_page(&[1, 2, 3], &fork), None);
+        // Page 4 has no rules, so it can be placed anywhere around the chain
+        assert_eq!(count_orderings(&[1, 2, 3, 4], &rules(&[(1, 2), (2, 3)])), OrderingCount::Exact(4));
+        assert_eq!(fixed_middle_page(&[1, 2, 3, 4, 5], &rules(&[(1, 3), (2, 3), (3, 4), (3, 5)])), Some(3));
+
+        let cycle = rules(&[(1, 2), (2, 3), (3, 1)]);
+        assert_eq!(count_orderings(&[1, 2, 3], &cycle), OrderingCount::Exact(0));
+        assert_eq!(fixed_middle_page(&[1, 2, 3], &cycle), None);
+    }
+
+    #[test]
+    fn count_bounds() {
+        let update: Vec<u32> = (1..=25).collect();
+        let factorial_25 = (1..=25).product();
+        assert_eq!(count_orderings(&update, &rules(&[])), OrderingCount::Bounds {
+            lower: factorial_25,
+            upper: factorial_25,
+        });
+
+        let chain: Vec<(u32, u32)> = update.windows(2).map(|w| (w[0], w[1])).collect();
+        assert_eq!(count_orderings(&update, &rules(&chain)), OrderingCount::Bounds { lower: 1, upper: 1 });
+
+        let OrderingCount::Bounds { lower, upper } = count_orderings(&update, &rules(&chain[..12])) else {
+            panic!("expected bounds");
+        };
+        assert!(lower <= upper);
+    }
+}
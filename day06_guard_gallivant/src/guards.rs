@@ -0,0 +1,156 @@
+// SPDX-FileCopyrightText: 2024 Petr Pucil <petr.pucil@seznam.cz>
+//
+// SPDX-License-Identifier: MIT
+
+use std::fmt;
+
+use utils::{Pos, Table};
+
+use crate::movement::MovementRule;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Guard {
+    pub pos: Pos,
+    pub dir: (isize, isize),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum GuardError {
+    NoGuard,
+    MultipleGuards(Vec<Pos>),
+}
+
+impl fmt::Display for GuardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GuardError::NoGuard => write!(f, "no guard (one of ^ > v <) found"),
+            GuardError::MultipleGuards(positions) => write!(f, "expected one guard, but found {}: {positions:?}", positions.len()),
+        }
+    }
+}
+
+fn dir_from_glyph(c: u8) -> Option<(isize, isize)> {
+    match c {
+        b'^' => Some((-1, 0)),
+        b'>' => Some((0, 1)),
+        b'v' => Some((1, 0)),
+        b'<' => Some((0, -1)),
+        _ => None,
+    }
+}
+
+pub fn glyph_from_dir(dir: (isize, isize)) -> u8 {
+    match dir {
+        (-1, 0) => b'^',
+        (0, 1) => b'>',
+        (1, 0) => b'v',
+        (0, -1) => b'<',
+        _ => panic!("invalid direction {dir:?}"),
+    }
+}
+
+pub fn find_guards(table: &Table) -> Vec<Guard> {
+    table
+        .all_positions()
+        .filter_map(|pos| dir_from_glyph(table[pos]).map(|dir| Guard { pos, dir }))
+        .collect()
+}
+
+pub fn find_guard(table: &Table) -> Result<Guard, GuardError> {
+    match find_guards(table).as_slice() {
+        [] => Err(GuardError::NoGuard),
+        &[guard] => Ok(guard),
+        guards => Err(GuardError::MultipleGuards(guards.iter().map(|g| g.pos).collect())),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CollisionKind {
+    SameCell,
+    // The guards swapped their positions, i.e. walked through each other
+    Crossed,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Collision {
+    pub tick: usize,
+    pub guards: (usize, usize),
+    pub pos: Pos,
+    pub kind: CollisionKind,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SimulationReport {
+    pub collisions: Vec<Collision>,
+    // The tick in which each guard left the map, `None` if it was still inside after `max_ticks`
+    pub exit_ticks: Vec<Option<usize>>,
+}
+
+// All guards act at the same time: in each tick, every guard still on the map either turns in
+// front of an obstacle or makes one step. Guards don't block each other, their collisions are
+// only reported. A guard stuck in a loop never leaves, hence the `max_ticks` limit.
+pub fn simulate_guards(table: &Table, mut guards: Vec<Guard>, rule: &dyn MovementRule, max_ticks: usize) -> SimulationReport {
+    let mut collisions = vec![];
+    let mut exit_ticks = vec![None; guards.len()];
+    let mut phases = vec![0; guards.len()];
+    for tick in 1..=max_ticks {
+        let prev_positions: Vec<Pos> = guards.iter().map(|g| g.pos).collect();
+        for ((guard, phase), exit_tick) in guards.iter_mut().zip(&mut phases).zip(&mut exit_ticks) {
+            if exit_tick.is_some() {
+                continue;
+            }
+            match table.move_from_pos(guard.pos, guard.dir, 1) {
+                None => *exit_tick = Some(tick),
+                Some(next_pos) if rule.is_obstacle(table[next_pos]) => (guard.dir, *phase) = rule.turn(guard.dir, *phase),
+                Some(next_pos) => guard.pos = rule.portal_exit(next_pos).unwrap_or(next_pos),
+            }
+        }
+
+        let active: Vec<usize> = (0..guards.len()).filter(|&i| exit_ticks[i].is_none()).collect();
+        if active.is_empty() {
+            break;
+        }
+        for (ai, &i) in active.iter().enumerate() {
+            for &j in &active[ai + 1..] {
+                if guards[i].pos == guards[j].pos {
+                    collisions.push(Collision { tick, guards: (i, j), pos: guards[i].pos, kind: CollisionKind::SameCell });
+                } else if guards[i].pos == prev_positions[j] && guards[j].pos == prev_positions[i] {
+                    collisions.push(Collision { tick, guards: (i, j), pos: guards[i].pos, kind: CollisionKind::Crossed });
+                }
+            }
+        }
+    }
+    SimulationReport { collisions, exit_ticks }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movement::ConfigurableRule;
+
+    #[test]
+    fn guard_glyphs() {
+        let table = Table::new(3, b".>.#v.".to_vec());
+        assert_eq!(find_guards(&table), vec![
+            Guard { pos: Pos::new(0, 1), dir: (0, 1) },
+            Guard { pos: Pos::new(1, 1), dir: (1, 0) },
+        ]);
+        assert_eq!(find_guard(&table), Err(GuardError::MultipleGuards(vec![Pos::new(0, 1), Pos::new(1, 1)])));
+        assert_eq!(find_guard(&Table::new(2, b"..#.".to_vec())), Err(GuardError::NoGuard));
+        assert_eq!(find_guard(&Table::new(2, b"..#<".to_vec())), Ok(Guard { pos: Pos::new(1, 1), dir: (0, -1) }));
+    }
+
+    #[test]
+    fn collisions() {
+        let table = Table::new(5, b"\
+>...<\
+.....\
+>..<.".to_vec());
+        let report = simulate_guards(&table, find_guards(&table), &ConfigurableRule::default(), 100);
+        assert_eq!(report.collisions, vec![
+            Collision { tick: 2, guards: (0, 1), pos: Pos::new(0, 2), kind: CollisionKind::SameCell },
+            Collision { tick: 2, guards: (2, 3), pos: Pos::new(2, 2), kind: CollisionKind::Crossed },
+        ]);
+        assert_eq!(report.exit_ticks, vec![Some(5), Some(5), Some(5), Some(4)]);
+    }
+}
@@ -0,0 +1,155 @@
+// SPDX-FileCopyrightText: 2024 Petr Pucil <petr.pucil@seznam.cz>
+//
+// SPDX-License-Identifier: MIT
+
+use utils::{Pos, Table};
+
+use crate::{dir_index, movement::MovementRule, DIRS};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Stop {
+    // In front of an obstacle, where the guard turns
+    Obstacle(Pos),
+    // On a portal cell, from which the guard is teleported
+    Portal(Pos),
+}
+
+// Precomputed "next obstacle in each direction", so that the guard can jump straight to the
+// position of the next turn instead of walking one cell at a time
+pub struct JumpTable<'a> {
+    rule: &'a dyn MovementRule,
+    cols: usize,
+    // `stops[dir_index(dir)][r * cols + c]` is where a guard at (r, c) heading in `dir` stops,
+    // or `None` if the guard leaves the map
+    stops: [Vec<Option<Stop>>; 4],
+}
+
+impl<'a> JumpTable<'a> {
+    pub fn new(table: &Table, rule: &'a dyn MovementRule) -> Self {
+        let cols = table.cols();
+        let stops = DIRS.map(|dir| {
+            let mut dir_stops = vec![None; table.rows() * cols];
+            let positions: Vec<Pos> = table.all_positions().collect();
+            // The neighbor in `dir` must be resolved first, which for `^` and `<` is the previous
+            // position in row-major order and for `v` and `>` the next one
+            let positions: Box<dyn Iterator<Item = &Pos>> = if dir.0 + dir.1 < 0 {
+                Box::new(positions.iter())
+            } else {
+                Box::new(positions.iter().rev())
+            };
+            for &pos in positions {
+                dir_stops[pos.r() * cols + pos.c()] = match table.move_from_pos(pos, dir, 1) {
+                    None => None,
+                    Some(next_pos) if rule.is_obstacle(table[next_pos]) => Some(Stop::Obstacle(pos)),
+                    Some(next_pos) if rule.portal_exit(next_pos).is_some() => Some(Stop::Portal(next_pos)),
+                    Some(next_pos) => dir_stops[next_pos.r() * cols + next_pos.c()],
+                };
+            }
+            dir_stops
+        });
+        Self { rule, cols, stops }
+    }
+
+    // Where the guard at `pos` heading in `dir` stops, taking into account extra `obstructions`
+    // that aren't in the table
+    fn next_stop(&self, pos: Pos, dir: (isize, isize), obstructions: &[Pos]) -> Option<Stop> {
+        let stop = self.stops[dir_index(dir)][pos.r() * self.cols + pos.c()];
+        let dist_ahead = |other: Pos| {
+            let (dr, dc) = other.diff(&pos);
+            let is_ahead = dr * dir.1 - dc * dir.0 == 0 && dr * dir.0 + dc * dir.1 > 0;
+            is_ahead.then_some(dr * dir.0 + dc * dir.1)
+        };
+        let stop_dist = stop.map(|stop| {
+            let (Stop::Obstacle(stop_pos) | Stop::Portal(stop_pos)) = stop;
+            dist_ahead(stop_pos).unwrap_or(0)
+        });
+        let nearest_obstruction = obstructions
+            .iter()
+            .filter_map(|&obstruction| dist_ahead(obstruction).map(|dist| (dist, obstruction)))
+            .filter(|&(dist, _)| stop_dist.is_none_or(|stop_dist| dist <= stop_dist))
+            .min_by_key(|&(dist, _)| dist);
+        match nearest_obstruction {
+            Some((_, obstruction)) => {
+                let r = obstruction.r().checked_add_signed(-dir.0).unwrap();
+                let c = obstruction.c().checked_add_signed(-dir.1).unwrap();
+                Some(Stop::Obstacle(Pos::new(r, c)))
+            }
+            None => stop,
+        }
+    }
+
+    pub fn is_stuck_in_loop(
+        &self,
+        mut guard_pos: Pos,
+        mut guard_dir: (isize, isize),
+        mut phase: usize,
+        obstructions: &[Pos],
+    ) -> bool {
+        // Only the states after turns and teleports are recorded (one bit per cell, direction and
+        // phase), since any loop has to repeat them. The guard can't get into more distinct
+        // states than there are bits, so the walk is bounded by their count.
+        let num_phases = self.rule.num_phases();
+        let num_states = self.stops[0].len() * DIRS.len() * num_phases;
+        let mut prev_states = vec![0_u64; num_states.div_ceil(64)];
+        for _ in 0..=num_states {
+            match self.next_stop(guard_pos, guard_dir, obstructions) {
+                None => return false,
+                Some(Stop::Obstacle(pos)) => {
+                    guard_pos = pos;
+                    (guard_dir, phase) = self.rule.turn(guard_dir, phase);
+                }
+                Some(Stop::Portal(pos)) => guard_pos = self.rule.portal_exit(pos).unwrap(),
+            }
+            let cell = guard_pos.r() * self.cols + guard_pos.c();
+            let state = (cell * DIRS.len() + dir_index(guard_dir)) * num_phases + phase;
+            let (word, mask) = (state / 64, 1 << (state % 64));
+            if prev_states[word] & mask != 0 {
+                return true;
+            }
+            prev_states[word] |= mask;
+        }
+        unreachable!("the guard can't be in more than {num_states} distinct states")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movement::{ConfigurableRule, Turn};
+
+    #[test]
+    fn stops() {
+        let table = Table::new(4, b"\
+.#..\
+...#\
+#^..\
+..#.".to_vec());
+        let rule = ConfigurableRule::default();
+        let jumps = JumpTable::new(&table, &rule);
+        let start = Pos::new(2, 1);
+        assert_eq!(jumps.next_stop(start, (-1, 0), &[]), Some(Stop::Obstacle(Pos::new(1, 1))));
+        assert_eq!(jumps.next_stop(start, (0, 1), &[]), None);
+        assert_eq!(jumps.next_stop(start, (0, -1), &[]), Some(Stop::Obstacle(start)));
+        assert_eq!(jumps.next_stop(start, (0, 1), &[Pos::new(2, 3)]), Some(Stop::Obstacle(Pos::new(2, 2))));
+        assert_eq!(jumps.next_stop(start, (0, 1), &[Pos::new(1, 2)]), None);
+        assert_eq!(jumps.next_stop(start, (0, 1), &[Pos::new(2, 3), Pos::new(2, 2)]), Some(Stop::Obstacle(start)));
+        // The obstruction behind the original obstacle doesn't matter
+        assert_eq!(jumps.next_stop(Pos::new(3, 0), (-1, 0), &[Pos::new(0, 0)]), Some(Stop::Obstacle(Pos::new(3, 0))));
+    }
+
+    #[test]
+    fn portals() {
+        // Walking along the row with the portals leads through them forever
+        let table = Table::new(5, b"\
+..#..\
+.1..1\
+..^.#\
+.....".to_vec());
+        let rule = ConfigurableRule::new(Turn::Right, b"#".to_vec()).with_portals(&table, b"1");
+        let jumps = JumpTable::new(&table, &rule);
+        assert_eq!(jumps.next_stop(Pos::new(1, 2), (0, -1), &[]), Some(Stop::Portal(Pos::new(1, 1))));
+        assert!(jumps.is_stuck_in_loop(Pos::new(1, 3), (0, -1), 0, &[]));
+        assert!(jumps.is_stuck_in_loop(Pos::new(2, 2), (-1, 0), 0, &[]));
+        assert!(!jumps.is_stuck_in_loop(Pos::new(2, 2), (1, 0), 0, &[]));
+    }
+}
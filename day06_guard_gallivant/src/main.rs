@@ -2,90 +2,171 @@
 //
 // SPDX-License-Identifier: MIT
 
-use std::{collections::HashSet, io};
+use std::{collections::HashSet, env, io};
 
+use guards::{find_guard, find_guards, simulate_guards, Guard};
+use jump_table::JumpTable;
+use movement::{ConfigurableRule, MovementRule, Turn};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use trace::{trace_route, write_loop_obstructions, write_trace, Cycle, Event, Format, Route};
 use utils::{Pos, Table};
 
+mod guards;
+mod jump_table;
+mod movement;
+mod trace;
+mod trap;
+
 fn main() {
+    // [guards | trace | trace-loops | min-trap] [--format csv|jsonl] [--max-obstructions <n>]
+    //     [--turn right|left|alternate|back] [--obstacles <glyphs>] [--portals <glyphs>]
+    let mut mode = None;
+    let mut format = Format::Csv;
+    let mut turn = Turn::Right;
+    let mut obstacle_glyphs = b"#".to_vec();
+    let mut portal_glyphs = vec![];
+    let mut max_obstructions = 2;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut option_value = || args.next().unwrap_or_else(|| panic!("missing value of {arg}"));
+        match arg.as_str() {
+            "--format" => {
+                let name = option_value();
+                format = Format::from_name(&name).unwrap_or_else(|| panic!("unknown format {name:?}"));
+            }
+            "--turn" => {
+                let name = option_value();
+                turn = Turn::from_name(&name).unwrap_or_else(|| panic!("unknown turn {name:?}"));
+            }
+            "--obstacles" => obstacle_glyphs = option_value().into_bytes(),
+            "--portals" => portal_glyphs = option_value().into_bytes(),
+            "--max-obstructions" => max_obstructions = option_value().parse().unwrap(),
+            _ if arg.starts_with("--") => panic!("unknown option {arg:?}"),
+            _ if mode.is_none() => mode = Some(arg),
+            _ => panic!("unexpected argument {arg:?}"),
+        }
+    }
+
     let input = Table::from_reader(io::stdin().lock());
-    // let res = solve_part1(input);
-    let res = solve_part2(input);
-    println!("{res}");
-}
+    let rule = ConfigurableRule::new(turn, obstacle_glyphs).with_portals(&input, &portal_glyphs);
+    if mode.as_deref() == Some("guards") {
+        print_guard_simulation(&input, &rule);
+        return;
+    }
 
-fn solve_part1(mut table: Table) -> usize {
-    let mut guard_pos = table.all_positions()
-        .find(|&pos| table[pos] == b'^')
-        .unwrap();
-    let mut guard_dir = (-1, 0);
-    let mut num_distinct_positions = 0;
-    loop {
-        if table[guard_pos] != b'X' {
-            num_distinct_positions += 1;
+    // The other modes follow a single guard
+    let guard = find_guard(&input).unwrap_or_else(|e| panic!("{e}"));
+    match mode.as_deref() {
+        None => {
+            // let res = solve_part1(&input, guard, &rule);
+            let res = solve_part2(&input, guard, &rule);
+            println!("{res}");
         }
-        table[guard_pos] = b'X';
-        let Some(next_pos) = table.move_from_pos(guard_pos, guard_dir, 1) else {
-            break;
-        };
-        if table[next_pos] == b'#' {
-            guard_dir = dir_right_hand(guard_dir);
-            continue;
+        Some("trace") => {
+            let route = trace_route(&input, guard, &rule, &[]);
+            write_trace(io::stdout().lock(), &route.records, format).unwrap();
         }
-        guard_pos = next_pos;
-    }
-    num_distinct_positions
-}
-
-fn solve_part2(mut table: Table) -> usize {
-    let mut guard_pos = table.all_positions()
-        .find(|&pos| table[pos] == b'^')
-        .unwrap();
-    let mut guard_dir = (-1, 0);
-    let mut num_loops = 0;
-    loop {
-        table[guard_pos] = b'X';
-        let Some(next_pos) = table.move_from_pos(guard_pos, guard_dir, 1) else {
-            break;
-        };
-        if table[next_pos] == b'#' {
-            guard_dir = dir_right_hand(guard_dir);
-            continue;
+        Some("trace-loops") => {
+            let jumps = JumpTable::new(&input, &rule);
+            let loops: Vec<(Pos, Cycle)> = find_obstruction_candidates(&input, guard, &rule)
+                .into_iter()
+                .filter(|c| jumps.is_stuck_in_loop(c.guard_pos, c.guard_dir, c.phase, &[c.obstruction]))
+                .map(|c| (c.obstruction, trace_route(&input, guard, &rule, &[c.obstruction]).cycle.unwrap()))
+                .collect();
+            write_loop_obstructions(io::stdout().lock(), &loops, format).unwrap();
         }
-        let orig_cell = table[next_pos];
-        if orig_cell == b'.' {
-            table[next_pos] = b'#';
-            if is_stuck_in_loop(&table, guard_pos, guard_dir) {
-                num_loops += 1;
-                table[next_pos] = b'@';
-            } else {
-                table[next_pos] = orig_cell;
+        Some("min-trap") => {
+            match trap::find_min_trap(&input, guard, &rule, max_obstructions) {
+                Some(obstructions) => {
+                    println!("{} obstructions", obstructions.len());
+                    for pos in obstructions {
+                        println!("{},{}", pos.r(), pos.c());
+                    }
+                }
+                None => println!("no trap with at most {max_obstructions} obstructions"),
             }
         }
-        guard_pos = next_pos;
+        Some(mode) => panic!("unknown mode {mode:?}, expected \"guards\", \"trace\", \"trace-loops\" or \"min-trap\""),
     }
-    num_loops
 }
 
-fn is_stuck_in_loop(table: &Table, mut guard_pos: Pos, mut guard_dir: (isize, isize)) -> bool {
-    let mut prev_states: HashSet<(Pos, (isize, isize))> = HashSet::new();
-    for _ in 0..10_000 {
-        {
-            let state = (guard_pos, guard_dir);
-            if prev_states.contains(&state) {
-                return true;
-            }
-            prev_states.insert(state);
+fn print_guard_simulation(table: &Table, rule: &dyn MovementRule) {
+    // Each guard alone either leaves or starts looping within this many ticks
+    let max_ticks = table.rows() * table.cols() * DIRS.len() * rule.num_phases();
+    let report = simulate_guards(table, find_guards(table), rule, max_ticks);
+    for c in &report.collisions {
+        println!("tick {}: guards {} and {} {:?} at {:?}", c.tick, c.guards.0, c.guards.1, c.kind, c.pos);
+    }
+    for (i, exit_tick) in report.exit_ticks.iter().enumerate() {
+        match exit_tick {
+            Some(tick) => println!("guard {i} left in tick {tick}"),
+            None => println!("guard {i} still inside after {max_ticks} ticks"),
         }
-        let Some(next_pos) = table.move_from_pos(guard_pos, guard_dir, 1) else {
-            return false;
-        };
-        if table[next_pos] == b'#' {
-            guard_dir = dir_right_hand(guard_dir);
+    }
+}
+
+fn solve_part1(table: &Table, guard: Guard, rule: &dyn MovementRule) -> usize {
+    let route = trace_route(table, guard, rule, &[]);
+    let distinct_positions: HashSet<Pos> = route.records.iter().map(|r| r.guard.pos).collect();
+    distinct_positions.len()
+}
+
+fn solve_part2(table: &Table, guard: Guard, rule: &dyn MovementRule) -> usize {
+    let jumps = JumpTable::new(table, rule);
+    let candidates = find_obstruction_candidates(table, guard, rule);
+    count_loop_obstructions(&jumps, &candidates)
+}
+
+// The guard state right before it would bump into the obstruction for the first time
+struct ObstructionCandidate {
+    obstruction: Pos,
+    guard_pos: Pos,
+    guard_dir: (isize, isize),
+    phase: usize,
+}
+
+fn find_obstruction_candidates(table: &Table, guard: Guard, rule: &dyn MovementRule) -> Vec<ObstructionCandidate> {
+    let route = trace_route(table, guard, rule, &[]);
+    route_obstruction_candidates(table, &route)
+}
+
+fn route_obstruction_candidates(table: &Table, route: &Route) -> Vec<ObstructionCandidate> {
+    let mut visited = vec![false; table.rows() * table.cols()];
+    let mut candidates = vec![];
+    for record in &route.records {
+        let Guard { pos: guard_pos, dir: guard_dir } = record.guard;
+        visited[guard_pos.r() * table.cols() + guard_pos.c()] = true;
+        if record.event != Event::Move {
             continue;
         }
-        guard_pos = next_pos;
+        let next_pos = table.move_from_pos(guard_pos, guard_dir, 1).unwrap();
+        // An obstruction can only be placed where the guard hasn't been yet, otherwise it would
+        // have changed the route before reaching the current position
+        if table[next_pos] == b'.' && !visited[next_pos.r() * table.cols() + next_pos.c()] {
+            candidates.push(ObstructionCandidate { obstruction: next_pos, guard_pos, guard_dir, phase: record.phase });
+        }
     }
-    unreachable!()
+    candidates
+}
+
+// The candidates are independent of each other, since the extra obstruction is only passed to
+// the immutable jump table instead of being written into the grid
+fn count_loop_obstructions(jumps: &JumpTable, candidates: &[ObstructionCandidate]) -> usize {
+    #[cfg(feature = "rayon")]
+    let candidates = candidates.par_iter();
+    #[cfg(not(feature = "rayon"))]
+    let candidates = candidates.iter();
+    candidates
+        .filter(|c| jumps.is_stuck_in_loop(c.guard_pos, c.guard_dir, c.phase, &[c.obstruction]))
+        .count()
+}
+
+// In the order of right-hand turns, starting with `^`
+const DIRS: [(isize, isize); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
+
+fn dir_index(dir: (isize, isize)) -> usize {
+    DIRS.iter().position(|&d| d == dir).unwrap()
 }
 
 fn dir_right_hand(dir: (isize, isize)) -> (isize, isize) {
@@ -96,6 +177,106 @@ fn dir_right_hand(dir: (isize, isize)) -> (isize, isize) {
 mod tests {
     use super::*;
 
+    const EXAMPLE: &[u8] = b"\
+....#.....
+.........#
+..........
+..#.......
+.......#..
+..........
+.#..^.....
+........#.
+#.........
+......#...
+";
+
+    #[test]
+    fn example() {
+        let table = Table::from_reader(EXAMPLE);
+        let rule = ConfigurableRule::default();
+        assert_eq!(solve_part1(&table, find_guard(&table).unwrap(), &rule), 41);
+        assert_eq!(solve_part2(&table, find_guard(&table).unwrap(), &rule), 6);
+    }
+
+    #[test]
+    fn rotated_example() {
+        // Rotating the map clockwise turns `^` into `>` without changing the route
+        let table = Table::from_reader(EXAMPLE);
+        let mut cells = vec![];
+        for c in 0..table.cols() {
+            for r in (0..table.rows()).rev() {
+                cells.push(match table[Pos::new(r, c)] {
+                    b'^' => b'>',
+                    cell => cell,
+                });
+            }
+        }
+        let rotated = Table::new(table.rows(), cells);
+        let rule = ConfigurableRule::default();
+        assert_eq!(solve_part1(&rotated, find_guard(&rotated).unwrap(), &rule), 41);
+        assert_eq!(solve_part2(&rotated, find_guard(&rotated).unwrap(), &rule), 6);
+    }
+
+    #[test]
+    fn mirrored_example_turning_left() {
+        // In a mirror image, right-hand turns become left-hand turns
+        let table = Table::from_reader(EXAMPLE);
+        let mut cells = vec![];
+        for r in 0..table.rows() {
+            for c in (0..table.cols()).rev() {
+                cells.push(match table[Pos::new(r, c)] {
+                    b'#' => b'O',
+                    cell => cell,
+                });
+            }
+        }
+        let mirrored = Table::new(table.cols(), cells);
+        let rule = ConfigurableRule::new(Turn::Left, b"O".to_vec());
+        assert_eq!(solve_part1(&mirrored, find_guard(&mirrored).unwrap(), &rule), 41);
+        assert_eq!(solve_part2(&mirrored, find_guard(&mirrored).unwrap(), &rule), 6);
+    }
+
+    // The guard spirals outwards with right-hand turns and then leaves the map
+    fn spiral_table(num_legs: usize) -> Table {
+        let size = 2 * num_legs + 5;
+        let mut table = Table::new(size, vec![b'.'; size * size]);
+        let start = Pos::new(size / 2, size / 2);
+        table[start] = b'^';
+        let mut pos = start;
+        let mut dir = (-1, 0);
+        for leg in 0..num_legs {
+            let leg_len = 2 * (leg / 2 + 1);
+            pos = table.move_from_pos(pos, dir, isize::try_from(leg_len).unwrap()).unwrap();
+            let obstacle = table.move_from_pos(pos, dir, 1).unwrap();
+            table[obstacle] = b'#';
+            dir = dir_right_hand(dir);
+        }
+        table
+    }
+
+    #[test]
+    fn long_route_without_loop() {
+        let table = spiral_table(144);
+        let rule = ConfigurableRule::default();
+        assert!(solve_part1(&table, find_guard(&table).unwrap(), &rule) > 10_000);
+        let start = table.all_positions().find(|&pos| table[pos] == b'^').unwrap();
+        assert!(!JumpTable::new(&table, &rule).is_stuck_in_loop(start, (-1, 0), 0, &[]));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_matches_sequential() {
+        let table = Table::from_reader(EXAMPLE);
+        let rule = ConfigurableRule::default();
+        let jumps = JumpTable::new(&table, &rule);
+        let candidates = find_obstruction_candidates(&table, find_guard(&table).unwrap(), &rule);
+        let sequential_count = candidates
+            .iter()
+            .filter(|c| jumps.is_stuck_in_loop(c.guard_pos, c.guard_dir, c.phase, &[c.obstruction]))
+            .count();
+        assert_eq!(count_loop_obstructions(&jumps, &candidates), sequential_count);
+    }
+
     #[test]
     fn right_hand() {
         assert_eq!(dir_right_hand((0, 1)), (1, 0));
@@ -110,7 +291,7 @@ mod tests {
             b'#', b'^', b'.', b'.',
             b'.', b'.', b'#', b'.',
         ]);
-        assert!(is_stuck_in_loop(&table, table.all_positions().find(|&pos| table[pos] == b'^').unwrap(), (-1, 0)));
+        assert!(JumpTable::new(&table, &ConfigurableRule::default()).is_stuck_in_loop(table.all_positions().find(|&pos| table[pos] == b'^').unwrap(), (-1, 0), 0, &[]));
 
         let table = Table::new(4, vec![
             b'.', b'#', b'.', b'.',
@@ -118,7 +299,7 @@ mod tests {
             b'.', b'^', b'.', b'.',
             b'.', b'.', b'#', b'.',
         ]);
-        assert!(!is_stuck_in_loop(&table, table.all_positions().find(|&pos| table[pos] == b'^').unwrap(), (-1, 0)));
+        assert!(!JumpTable::new(&table, &ConfigurableRule::default()).is_stuck_in_loop(table.all_positions().find(|&pos| table[pos] == b'^').unwrap(), (-1, 0), 0, &[]));
 
         let table = Table::new(6, vec![
             b'.', b'#', b'.', b'#', b'.', b'.',
@@ -127,6 +308,6 @@ mod tests {
             b'.', b'.', b'#', b'.', b'.', b'.',
             b'.', b'.', b'.', b'.', b'#', b'.',
         ]);
-        assert!(is_stuck_in_loop(&table, table.all_positions().find(|&pos| table[pos] == b'v').unwrap(), (1, 0)));
+        assert!(JumpTable::new(&table, &ConfigurableRule::default()).is_stuck_in_loop(table.all_positions().find(|&pos| table[pos] == b'v').unwrap(), (1, 0), 0, &[]));
     }
 }
@@ -0,0 +1,126 @@
+// SPDX-FileCopyrightText: 2024 Petr Pucil <petr.pucil@seznam.cz>
+//
+// SPDX-License-Identifier: MIT
+
+use std::collections::HashMap;
+
+use utils::{Pos, Table};
+
+use crate::dir_right_hand;
+
+// How the guard reacts to the map. The rule may have a small internal state (a "phase", e.g.
+// which way to turn next), which is part of the guard state for the purpose of loop detection.
+pub trait MovementRule: Sync {
+    fn is_obstacle(&self, cell: u8) -> bool;
+
+    fn num_phases(&self) -> usize {
+        1
+    }
+
+    // The new direction and phase after bumping into an obstacle
+    fn turn(&self, dir: (isize, isize), phase: usize) -> ((isize, isize), usize);
+
+    // Where the guard ends up after stepping onto `pos`, if there is a portal
+    fn portal_exit(&self, _pos: Pos) -> Option<Pos> {
+        None
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Turn {
+    #[default]
+    Right,
+    Left,
+    // Right, left, right, ...
+    Alternate,
+    // Reverse the direction
+    Back,
+}
+
+impl Turn {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "right" => Some(Turn::Right),
+            "left" => Some(Turn::Left),
+            "alternate" => Some(Turn::Alternate),
+            "back" => Some(Turn::Back),
+            _ => None,
+        }
+    }
+}
+
+// The default is the rule of the puzzle: turn right in front of `#`
+#[derive(Debug)]
+pub struct ConfigurableRule {
+    turn: Turn,
+    obstacle_glyphs: Vec<u8>,
+    portals: HashMap<Pos, Pos>,
+}
+
+impl Default for ConfigurableRule {
+    fn default() -> Self {
+        Self::new(Turn::Right, b"#".to_vec())
+    }
+}
+
+impl ConfigurableRule {
+    pub fn new(turn: Turn, obstacle_glyphs: Vec<u8>) -> Self {
+        Self { turn, obstacle_glyphs, portals: HashMap::new() }
+    }
+
+    // Each of the `portal_glyphs` must appear exactly twice in the table, stepping onto one of
+    // these cells teleports the guard to the other one
+    pub fn with_portals(mut self, table: &Table, portal_glyphs: &[u8]) -> Self {
+        for &glyph in portal_glyphs {
+            let positions: Vec<Pos> = table.all_positions().filter(|&pos| table[pos] == glyph).collect();
+            assert_eq!(positions.len(), 2, "portal {:?} must appear exactly twice", glyph as char);
+            self.portals.insert(positions[0], positions[1]);
+            self.portals.insert(positions[1], positions[0]);
+        }
+        self
+    }
+}
+
+impl MovementRule for ConfigurableRule {
+    fn is_obstacle(&self, cell: u8) -> bool {
+        self.obstacle_glyphs.contains(&cell)
+    }
+
+    fn num_phases(&self) -> usize {
+        match self.turn {
+            Turn::Alternate => 2,
+            _ => 1,
+        }
+    }
+
+    fn turn(&self, dir: (isize, isize), phase: usize) -> ((isize, isize), usize) {
+        match (self.turn, phase) {
+            (Turn::Right, _) | (Turn::Alternate, 0) => (dir_right_hand(dir), (phase + 1) % self.num_phases()),
+            (Turn::Left, _) | (Turn::Alternate, _) => (dir_left_hand(dir), (phase + 1) % self.num_phases()),
+            (Turn::Back, _) => ((-dir.0, -dir.1), phase),
+        }
+    }
+
+    fn portal_exit(&self, pos: Pos) -> Option<Pos> {
+        self.portals.get(&pos).copied()
+    }
+}
+
+fn dir_left_hand(dir: (isize, isize)) -> (isize, isize) {
+    (-dir.1, dir.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turns() {
+        let alternate = ConfigurableRule::new(Turn::Alternate, b"#".to_vec());
+        assert_eq!(alternate.turn((-1, 0), 0), ((0, 1), 1));
+        assert_eq!(alternate.turn((0, 1), 1), ((-1, 0), 0));
+        let back = ConfigurableRule::new(Turn::Back, b"#".to_vec());
+        assert_eq!(back.turn((0, 1), 0), ((0, -1), 0));
+        assert_eq!(dir_left_hand((-1, 0)), (0, -1));
+    }
+}
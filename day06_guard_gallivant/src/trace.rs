@@ -0,0 +1,182 @@
+// SPDX-FileCopyrightText: 2024 Petr Pucil <petr.pucil@seznam.cz>
+//
+// SPDX-License-Identifier: MIT
+
+use std::io::{self, Write};
+
+use utils::{Pos, Table};
+
+use crate::{dir_index, guards::{glyph_from_dir, Guard}, movement::MovementRule, DIRS};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    Move,
+    Turn,
+    // The guard steps onto a portal and appears at the other end
+    Teleport,
+    Exit,
+    // The guard is in a state it has already been in, the route continues the same way forever
+    Loop,
+}
+
+impl Event {
+    fn name(self) -> &'static str {
+        match self {
+            Event::Move => "move",
+            Event::Turn => "turn",
+            Event::Teleport => "teleport",
+            Event::Exit => "exit",
+            Event::Loop => "loop",
+        }
+    }
+}
+
+// The guard state at the given step and what the guard does next
+#[derive(Debug, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub step: usize,
+    pub guard: Guard,
+    pub phase: usize,
+    pub event: Event,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Cycle {
+    pub entry_step: usize,
+    pub entry: Guard,
+    pub len: usize,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Route {
+    pub records: Vec<TraceRecord>,
+    pub cycle: Option<Cycle>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Format {
+    Csv,
+    JsonLines,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "csv" => Some(Format::Csv),
+            "jsonl" => Some(Format::JsonLines),
+            _ => None,
+        }
+    }
+}
+
+// Walks the route one cell at a time, treating the `obstructions` as extra obstacles
+pub fn trace_route(table: &Table, mut guard: Guard, rule: &dyn MovementRule, obstructions: &[Pos]) -> Route {
+    let num_phases = rule.num_phases();
+    let mut phase = 0;
+    // The step in which the guard was first in each state (cell, direction and phase)
+    let mut first_steps: Vec<Option<usize>> = vec![None; table.rows() * table.cols() * DIRS.len() * num_phases];
+    let mut records = vec![];
+    // Each step either ends the route or moves the guard to a new state
+    for step in 0..=first_steps.len() {
+        let cell = guard.pos.r() * table.cols() + guard.pos.c();
+        let state = (cell * DIRS.len() + dir_index(guard.dir)) * num_phases + phase;
+        if let Some(entry_step) = first_steps[state] {
+            records.push(TraceRecord { step, guard, phase, event: Event::Loop });
+            let cycle = Cycle { entry_step, entry: guard, len: step - entry_step };
+            return Route { records, cycle: Some(cycle) };
+        }
+        first_steps[state] = Some(step);
+
+        let Some(next_pos) = table.move_from_pos(guard.pos, guard.dir, 1) else {
+            records.push(TraceRecord { step, guard, phase, event: Event::Exit });
+            return Route { records, cycle: None };
+        };
+        if rule.is_obstacle(table[next_pos]) || obstructions.contains(&next_pos) {
+            records.push(TraceRecord { step, guard, phase, event: Event::Turn });
+            (guard.dir, phase) = rule.turn(guard.dir, phase);
+        } else if let Some(portal_exit) = rule.portal_exit(next_pos) {
+            records.push(TraceRecord { step, guard, phase, event: Event::Teleport });
+            guard.pos = portal_exit;
+        } else {
+            records.push(TraceRecord { step, guard, phase, event: Event::Move });
+            guard.pos = next_pos;
+        }
+    }
+    unreachable!("the guard can't be in more than {} distinct states", first_steps.len())
+}
+
+pub fn write_trace<W: Write>(mut wtr: W, records: &[TraceRecord], format: Format) -> io::Result<()> {
+    if let Format::Csv = format {
+        writeln!(wtr, "step,row,col,direction,event")?;
+    }
+    for record in records {
+        let TraceRecord { step, guard, event, .. } = record;
+        let (row, col) = (guard.pos.r(), guard.pos.c());
+        let dir = glyph_from_dir(guard.dir) as char;
+        let event = event.name();
+        match format {
+            Format::Csv => writeln!(wtr, "{step},{row},{col},{dir},{event}")?,
+            Format::JsonLines => writeln!(
+                wtr,
+                r#"{{"step":{step},"row":{row},"col":{col},"direction":"{dir}","event":"{event}"}}"#,
+            )?,
+        }
+    }
+    Ok(())
+}
+
+pub fn write_loop_obstructions<W: Write>(mut wtr: W, loops: &[(Pos, Cycle)], format: Format) -> io::Result<()> {
+    if let Format::Csv = format {
+        writeln!(wtr, "row,col,loop_length,entry_step,entry_row,entry_col,entry_direction")?;
+    }
+    for (obstruction, cycle) in loops {
+        let (row, col) = (obstruction.r(), obstruction.c());
+        let Cycle { entry_step, entry, len } = cycle;
+        let (entry_row, entry_col) = (entry.pos.r(), entry.pos.c());
+        let entry_dir = glyph_from_dir(entry.dir) as char;
+        match format {
+            Format::Csv => writeln!(wtr, "{row},{col},{len},{entry_step},{entry_row},{entry_col},{entry_dir}")?,
+            Format::JsonLines => writeln!(
+                wtr,
+                r#"{{"row":{row},"col":{col},"loop_length":{len},"entry_step":{entry_step},"entry_row":{entry_row},"entry_col":{entry_col},"entry_direction":"{entry_dir}"}}"#,
+            )?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movement::ConfigurableRule;
+
+    #[test]
+    fn route_with_loop() {
+        let table = Table::new(4, b"\
+.#..\
+...#\
+#^..\
+..#.".to_vec());
+        let start = Guard { pos: Pos::new(2, 1), dir: (-1, 0) };
+        let route = trace_route(&table, start, &ConfigurableRule::default(), &[]);
+        assert_eq!(route.cycle, Some(Cycle { entry_step: 0, entry: start, len: 8 }));
+        let events: Vec<Event> = route.records.iter().map(|r| r.event).collect();
+        assert_eq!(events, [
+            Event::Move, Event::Turn, Event::Move, Event::Turn,
+            Event::Move, Event::Turn, Event::Move, Event::Turn,
+            Event::Loop,
+        ]);
+
+        let route = trace_route(&table, start, &ConfigurableRule::default(), &[Pos::new(1, 1)]);
+        assert_eq!(route.cycle, None);
+        let mut csv = vec![];
+        write_trace(&mut csv, &route.records, Format::Csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "\
+step,row,col,direction,event
+0,2,1,^,turn
+1,2,1,>,move
+2,2,2,>,move
+3,2,3,>,exit
+");
+    }
+}
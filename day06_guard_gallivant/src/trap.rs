@@ -0,0 +1,82 @@
+// SPDX-FileCopyrightText: 2024 Petr Pucil <petr.pucil@seznam.cz>
+//
+// SPDX-License-Identifier: MIT
+
+use utils::{Pos, Table};
+
+use crate::{
+    guards::Guard,
+    jump_table::JumpTable,
+    movement::MovementRule,
+    route_obstruction_candidates,
+    trace::trace_route,
+};
+
+// The smallest set of added obstructions (at most `max_obstructions`) that makes the guard loop,
+// which on a finite map is the same as keeping the guard from leaving. Each obstruction of a
+// minimal set is bumped into, so the first one lies on the original route, the second one on the
+// route with the first one added and so on, which is what the search goes through.
+pub fn find_min_trap(table: &Table, guard: Guard, rule: &dyn MovementRule, max_obstructions: usize) -> Option<Vec<Pos>> {
+    if trace_route(table, guard, rule, &[]).cycle.is_some() {
+        return Some(vec![]);
+    }
+    let jumps = JumpTable::new(table, rule);
+    // Iterative deepening, so that the first set found is the smallest one
+    (1..=max_obstructions).find_map(|num_obstructions| {
+        let mut obstructions = vec![];
+        search(table, guard, rule, &jumps, &mut obstructions, num_obstructions).then_some(obstructions)
+    })
+}
+
+fn search(
+    table: &Table,
+    guard: Guard,
+    rule: &dyn MovementRule,
+    jumps: &JumpTable,
+    obstructions: &mut Vec<Pos>,
+    num_remaining: usize,
+) -> bool {
+    let route = trace_route(table, guard, rule, obstructions);
+    for c in route_obstruction_candidates(table, &route) {
+        obstructions.push(c.obstruction);
+        let is_trapped = if num_remaining == 1 {
+            jumps.is_stuck_in_loop(c.guard_pos, c.guard_dir, c.phase, obstructions)
+        } else {
+            search(table, guard, rule, jumps, obstructions, num_remaining - 1)
+        };
+        if is_trapped {
+            return true;
+        }
+        obstructions.pop();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{guards::find_guard, movement::ConfigurableRule};
+
+    #[test]
+    fn min_trap() {
+        // Turning right in an empty room needs an obstruction for each of the 4 turns of a loop
+        let table = Table::new(4, b"\
+....\
+.^..\
+....".to_vec());
+        let rule = ConfigurableRule::default();
+        let guard = find_guard(&table).unwrap();
+        assert_eq!(find_min_trap(&table, guard, &rule, 3), None);
+        let trap = find_min_trap(&table, guard, &rule, 4).unwrap();
+        assert_eq!(trap.len(), 4);
+        let route = trace_route(&table, guard, &rule, &trap);
+        assert!(route.cycle.is_some());
+
+        let table = Table::new(4, b"\
+.#..\
+...#\
+#^..\
+..#.".to_vec());
+        assert_eq!(find_min_trap(&table, find_guard(&table).unwrap(), &rule, 3), Some(vec![]));
+    }
+}
@@ -0,0 +1,397 @@
+// SPDX-FileCopyrightText: 2024 Petr Pucil <petr.pucil@seznam.cz>
+//
+// SPDX-License-Identifier: MIT
+
+use std::{collections::HashSet, fmt};
+
+use crate::{
+    can_reach_target_result,
+    num::{Num, Overflow},
+    operators::Operator,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Evaluation {
+    // As in the puzzle
+    LeftToRight,
+    // Operators with a higher precedence are applied first, e.g. `*` before `+`
+    Precedence,
+    // The expression may be parenthesized in any way
+    AnyParens,
+    // The operands may be reordered, the expression is then evaluated from left to right
+    AnyOrder,
+}
+
+impl Evaluation {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "left-to-right" => Some(Evaluation::LeftToRight),
+            "precedence" => Some(Evaluation::Precedence),
+            "any-parens" => Some(Evaluation::AnyParens),
+            "any-order" => Some(Evaluation::AnyOrder),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EvaluationError {
+    Overflow,
+    // Only with `Evaluation::AnyOrder`, see `MAX_REORDERED_OPERANDS`
+    TooManyOperands,
+}
+
+impl From<Overflow> for EvaluationError {
+    fn from(_: Overflow) -> Self {
+        EvaluationError::Overflow
+    }
+}
+
+impl fmt::Display for EvaluationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvaluationError::Overflow => write!(f, "overflow"),
+            EvaluationError::TooManyOperands => write!(f, "can't reorder more than {MAX_REORDERED_OPERANDS} operands"),
+        }
+    }
+}
+
+pub fn can_reach_target_result_with<N: Num>(
+    operands: &[N],
+    target_result: &N,
+    operators: &[&dyn Operator<N>],
+    evaluation: Evaluation,
+) -> Result<bool, EvaluationError> {
+    let res = match evaluation {
+        Evaluation::LeftToRight => can_reach_target_result(operands, target_result, operators),
+        Evaluation::Precedence => can_reach_with_precedence(operands, target_result, operators),
+        Evaluation::AnyParens => can_reach_with_any_parens(operands, target_result, operators),
+        Evaluation::AnyOrder if operands.len() > MAX_REORDERED_OPERANDS => return Err(EvaluationError::TooManyOperands),
+        Evaluation::AnyOrder => can_reach_in_any_order(operands, target_result, operators),
+    };
+    Ok(res?)
+}
+
+// The operands and operators (as indices into `operators`) that can't be applied yet, because an
+// operator with a higher precedence may still follow, and the last operand
+type PendingExpression<N> = (Vec<(N, usize)>, N);
+
+// Goes through the operands from left to right like a shunting-yard evaluator, keeping the set of
+// all distinct partially evaluated expressions
+fn can_reach_with_precedence<N: Num>(operands: &[N], target_result: &N, operators: &[&dyn Operator<N>]) -> Result<bool, Overflow> {
+    let prune = can_prune_above_target(operands, operators);
+    let mut is_overflow = false;
+    let mut expressions: HashSet<PendingExpression<N>> = HashSet::from([(vec![], operands[0].clone())]);
+    for operand in &operands[1..] {
+        let mut next_expressions = HashSet::new();
+        for (pending, last) in &expressions {
+            for (op_index, &op) in operators.iter().enumerate() {
+                let mut pending = pending.clone();
+                match reduce(&mut pending, last.clone(), Some(op), operators) {
+                    // The other pending values were already checked when they were pushed, and
+                    // they only grow when reduced
+                    Ok(Some(reduced)) if prune && (reduced > *target_result || operand > target_result) => {}
+                    Ok(Some(reduced)) => {
+                        pending.push((reduced, op_index));
+                        next_expressions.insert((pending, operand.clone()));
+                    }
+                    Ok(None) => {}
+                    Err(Overflow) if prune => {}
+                    Err(Overflow) => is_overflow = true,
+                }
+            }
+        }
+        expressions = next_expressions;
+    }
+    for (mut pending, last) in expressions {
+        match reduce(&mut pending, last, None, operators) {
+            Ok(Some(result)) if result == *target_result => return Ok(true),
+            Ok(_) => {}
+            Err(Overflow) => is_overflow = true,
+        }
+    }
+    if is_overflow {
+        Err(Overflow)
+    } else {
+        Ok(false)
+    }
+}
+
+// Applies the pending operators that bind at least as tightly as `next_op` (all if it's `None`),
+// returning the value that the next operator will be applied to
+fn reduce<N: Num>(
+    pending: &mut Vec<(N, usize)>,
+    mut last: N,
+    next_op: Option<&dyn Operator<N>>,
+    operators: &[&dyn Operator<N>],
+) -> Result<Option<N>, Overflow> {
+    while let Some((lhs, op_index)) = pending.last() {
+        let op = operators[*op_index];
+        if let Some(next_op) = next_op {
+            let binds_tighter = op.precedence() > next_op.precedence()
+                || (op.precedence() == next_op.precedence() && !next_op.is_right_associative());
+            if !binds_tighter {
+                break;
+            }
+        }
+        let Some(value) = op.apply(lhs, &last)? else {
+            return Ok(None);
+        };
+        last = value;
+        pending.pop();
+    }
+    Ok(Some(last))
+}
+
+// Interval DP: the values of each run of consecutive operands are the values of its left and right
+// part combined by any operator, for every way to split it
+fn can_reach_with_any_parens<N: Num>(operands: &[N], target_result: &N, operators: &[&dyn Operator<N>]) -> Result<bool, Overflow> {
+    let n = operands.len();
+    let prune = can_prune_above_target(operands, operators);
+    let mut is_overflow = false;
+    // `values[i][j]` are the values of `operands[i..=j]`
+    let mut values: Vec<Vec<HashSet<N>>> = vec![vec![HashSet::new(); n]; n];
+    for (i, operand) in operands.iter().enumerate() {
+        values[i][i].insert(operand.clone());
+    }
+    for len in 2..=n {
+        for i in 0..=n - len {
+            let j = i + len - 1;
+            let mut run_values = HashSet::new();
+            for k in i..j {
+                for lhs in &values[i][k] {
+                    for rhs in &values[k + 1][j] {
+                        for op in operators {
+                            match op.apply(lhs, rhs) {
+                                Ok(Some(value)) if prune && value > *target_result => {}
+                                Ok(Some(value)) => {
+                                    run_values.insert(value);
+                                }
+                                Ok(None) => {}
+                                Err(Overflow) if prune => {}
+                                Err(Overflow) => is_overflow = true,
+                            }
+                        }
+                    }
+                }
+            }
+            values[i][j] = run_values;
+        }
+    }
+    if values[0][n - 1].contains(target_result) {
+        Ok(true)
+    } else if is_overflow {
+        Err(Overflow)
+    } else {
+        Ok(false)
+    }
+}
+
+// There are `2^n` subsets, each with up to millions of values when the target is out of reach, so
+// already 9 operands can take half a minute
+pub const MAX_REORDERED_OPERANDS: usize = 8;
+
+// Bitmask DP over subsets of operands: the values of a subset are the values of the subset without
+// one of its operands, combined with that operand (which comes last) by any operator
+fn can_reach_in_any_order<N: Num>(operands: &[N], target_result: &N, operators: &[&dyn Operator<N>]) -> Result<bool, Overflow> {
+    let n = operands.len();
+    let prune = can_prune_above_target(operands, operators);
+    let mut is_overflow = false;
+    let mut values: Vec<HashSet<N>> = vec![HashSet::new(); 1 << n];
+    for (i, operand) in operands.iter().enumerate() {
+        values[1 << i].insert(operand.clone());
+    }
+    // Subsets are visited in increasing order, so all of their proper subsets come before them
+    for subset in 1_usize..1 << n {
+        if subset.is_power_of_two() {
+            continue;
+        }
+        let mut subset_values = HashSet::new();
+        for (last, operand) in operands.iter().enumerate().filter(|&(i, _)| subset & 1 << i != 0) {
+            for lhs in &values[subset & !(1 << last)] {
+                for op in operators {
+                    match op.apply(lhs, operand) {
+                        Ok(Some(value)) if prune && value > *target_result => {}
+                        Ok(Some(value)) => {
+                            subset_values.insert(value);
+                        }
+                        Ok(None) => {}
+                        // The value would be above the target anyway
+                        Err(Overflow) if prune => {}
+                        Err(Overflow) => is_overflow = true,
+                    }
+                }
+            }
+        }
+        values[subset] = subset_values;
+    }
+    if values[(1 << n) - 1].contains(target_result) {
+        Ok(true)
+    } else if is_overflow {
+        Err(Overflow)
+    } else {
+        Ok(false)
+    }
+}
+
+// Whether values above the target can be dropped, because no operator can bring them back down
+fn can_prune_above_target<N: Num>(operands: &[N], operators: &[&dyn Operator<N>]) -> bool {
+    let zero = N::from_u32(0);
+    operands.iter().all(|operand| *operand != zero) && operators.iter().all(|op| op.never_decreases())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operators::{self, Add, Concat, Mul};
+
+    // All values of all operator sequences, the expressions are evaluated by splitting them at
+    // the operator that is applied last
+    fn brute_force_values(operands: &[u64], operators: &[&dyn Operator<u64>], evaluation: Evaluation) -> Vec<u64> {
+        fn evaluate(operands: &[u64], ops: &[&dyn Operator<u64>], evaluation: Evaluation) -> Vec<Option<u64>> {
+            if ops.is_empty() {
+                return vec![Some(operands[0])];
+            }
+            let split_indices: Vec<usize> = match evaluation {
+                Evaluation::LeftToRight => vec![ops.len() - 1],
+                Evaluation::Precedence => {
+                    let min_precedence = ops.iter().map(|op| op.precedence()).min().unwrap();
+                    let mut lowest = (0..ops.len()).filter(|&i| ops[i].precedence() == min_precedence);
+                    if ops.iter().any(|op| op.precedence() == min_precedence && op.is_right_associative()) {
+                        vec![lowest.next().unwrap()]
+                    } else {
+                        vec![lowest.next_back().unwrap()]
+                    }
+                }
+                Evaluation::AnyParens => (0..ops.len()).collect(),
+                Evaluation::AnyOrder => unreachable!("reordering is brute-forced by permuting the operands"),
+            };
+            let mut values = vec![];
+            for k in split_indices {
+                for lhs in evaluate(&operands[..=k], &ops[..k], evaluation) {
+                    for rhs in evaluate(&operands[k + 1..], &ops[k + 1..], evaluation) {
+                        let value = match (lhs, rhs) {
+                            (Some(lhs), Some(rhs)) => ops[k].apply(&lhs, &rhs).unwrap_or(None),
+                            _ => None,
+                        };
+                        values.push(value);
+                    }
+                }
+            }
+            values
+        }
+
+        let mut values = vec![];
+        let mut ops = vec![0; operands.len() - 1];
+        loop {
+            let seq: Vec<&dyn Operator<u64>> = ops.iter().map(|&i| operators[i]).collect();
+            values.extend(evaluate(operands, &seq, evaluation).into_iter().flatten());
+            // Next operator sequence, like incrementing a number in base `operators.len()`
+            let Some(i) = ops.iter().position(|&i| i + 1 < operators.len()) else {
+                return values;
+            };
+            ops[i] += 1;
+            ops[..i].fill(0);
+        }
+    }
+
+    #[test]
+    fn precedence_and_parens() {
+        let operators: [&dyn Operator<u64>; 2] = [&Add, &Mul];
+        // 2 + 3 * 4
+        assert_eq!(can_reach_target_result_with(&[2, 3, 4], &14, &operators, Evaluation::Precedence), Ok(true));
+        assert_eq!(can_reach_target_result_with(&[2, 3, 4], &20, &operators, Evaluation::Precedence), Ok(false));
+        assert_eq!(can_reach_target_result_with(&[2, 3, 4], &20, &operators, Evaluation::LeftToRight), Ok(true));
+        // 2 * (3 + 4)
+        assert_eq!(can_reach_target_result_with(&[2, 3, 4], &14, &operators, Evaluation::AnyParens), Ok(true));
+        assert_eq!(can_reach_target_result_with(&[2, 3, 4], &20, &operators, Evaluation::AnyParens), Ok(true));
+        assert_eq!(can_reach_target_result_with(&[2, 3, 4], &21, &operators, Evaluation::AnyParens), Ok(false));
+        // 1 || 2 * 3 is 36 with precedence and 1 || 6 = 16 with parentheses
+        let operators: [&dyn Operator<u64>; 2] = [&Mul, &Concat];
+        assert_eq!(can_reach_target_result_with(&[1, 2, 3], &16, &operators, Evaluation::Precedence), Ok(false));
+        assert_eq!(can_reach_target_result_with(&[1, 2, 3], &16, &operators, Evaluation::AnyParens), Ok(true));
+        // The values above the target are dropped, so the longer concatenations don't overflow and
+        // there are few enough values for 12 operands
+        let operators: [&dyn Operator<u64>; 3] = [&Add, &Mul, &Concat];
+        let operands = [11, 22, 33, 44, 55, 66, 77, 88, 99, 12, 13, 14];
+        assert_eq!(can_reach_target_result_with(&operands, &123456789, &operators, Evaluation::Precedence), Ok(false));
+        assert_eq!(can_reach_target_result_with(&operands, &99999, &operators, Evaluation::AnyParens), Ok(true));
+    }
+
+    fn permutations(operands: &[u64]) -> Vec<Vec<u64>> {
+        if operands.is_empty() {
+            return vec![vec![]];
+        }
+        let mut res = vec![];
+        for i in 0..operands.len() {
+            let mut rest = operands.to_vec();
+            let first = rest.remove(i);
+            for mut perm in permutations(&rest) {
+                perm.insert(0, first);
+                res.push(perm);
+            }
+        }
+        res
+    }
+
+    #[test]
+    fn any_order() {
+        let operators: [&dyn Operator<u64>; 3] = [&Add, &Mul, &Concat];
+        // 83: 17 5 can't be solved in the given order, but 8 || 3 could
+        assert_eq!(can_reach_target_result_with(&[5, 17], &22, &operators, Evaluation::LeftToRight), Ok(true));
+        assert_eq!(can_reach_target_result_with(&[17, 5], &517, &operators, Evaluation::LeftToRight), Ok(false));
+        assert_eq!(can_reach_target_result_with(&[17, 5], &517, &operators, Evaluation::AnyOrder), Ok(true));
+        // 3 || 1 * 2 + 4
+        assert_eq!(can_reach_target_result_with(&[4, 2, 3, 1], &66, &operators, Evaluation::AnyOrder), Ok(true));
+        assert_eq!(can_reach_target_result_with(&[4, 2, 3, 1], &66, &operators, Evaluation::LeftToRight), Ok(false));
+        // 1 * 2 * ... * 8, only feasible because the values above the target are dropped
+        let operands: Vec<u64> = (1..=MAX_REORDERED_OPERANDS as u64).collect();
+        let product = operands.iter().product();
+        assert_eq!(can_reach_target_result_with(&operands, &product, &operators, Evaluation::AnyOrder), Ok(true));
+        let operands = vec![1; MAX_REORDERED_OPERANDS + 1];
+        assert_eq!(
+            can_reach_target_result_with(&operands, &1, &operators, Evaluation::AnyOrder),
+            Err(EvaluationError::TooManyOperands)
+        );
+    }
+
+    #[test]
+    fn brute_force_cross_check() {
+        let all_operators = operators::all::<u64>();
+        // Simple linear congruential generator, so that the test is reproducible
+        let mut seed = 7_u64;
+        let mut next_random = |bound: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) % bound
+        };
+        for _ in 0..60 {
+            let operators: Vec<&dyn Operator<u64>> =
+                all_operators.iter().copied().filter(|_| next_random(2) == 0).collect();
+            if operators.is_empty() {
+                continue;
+            }
+            let num_operands = 1 + next_random(4) as usize;
+            let operands: Vec<u64> = (0..num_operands).map(|_| next_random(5)).collect();
+            for evaluation in [Evaluation::LeftToRight, Evaluation::Precedence, Evaluation::AnyParens] {
+                let values = brute_force_values(&operands, &operators, evaluation);
+                check_against_brute_force(&operands, &operators, evaluation, &values);
+            }
+            let values: Vec<u64> = permutations(&operands)
+                .iter()
+                .flat_map(|perm| brute_force_values(perm, &operators, Evaluation::LeftToRight))
+                .collect();
+            check_against_brute_force(&operands, &operators, Evaluation::AnyOrder, &values);
+        }
+    }
+
+    fn check_against_brute_force(operands: &[u64], operators: &[&dyn Operator<u64>], evaluation: Evaluation, values: &[u64]) {
+        for target_result in (0..40).chain(values.iter().copied()) {
+            let is_reachable = values.contains(&target_result);
+            match can_reach_target_result_with(operands, &target_result, operators, evaluation) {
+                Ok(res) => assert_eq!(res, is_reachable, "{operands:?} {target_result} {evaluation:?}"),
+                // The brute force doesn't report overflows, so it must agree that the target can't be reached
+                Err(_) => assert!(!is_reachable, "{operands:?} {target_result} {evaluation:?}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,219 @@
+// SPDX-FileCopyrightText: 2024 Petr Pucil <petr.pucil@seznam.cz>
+//
+// SPDX-License-Identifier: MIT
+
+use std::collections::HashMap;
+
+use crate::{
+    num::{Num, Overflow},
+    operators::{Inverse, Operator},
+};
+
+// Up to `max_solutions` operator sequences reaching the target result, where the i-th operator of
+// a sequence goes between the operands i and i + 1
+pub fn find_solutions<'a, N: Num>(
+    operands: &[N],
+    target_result: &N,
+    operators: &[&'a dyn Operator<N>],
+    max_solutions: usize,
+) -> Result<Vec<Vec<&'a dyn Operator<N>>>, Overflow> {
+    let mut search =
+        SolutionSearch { operators, max_solutions, prefix: vec![], suffix: vec![], solutions: vec![], overflow: false };
+    search.search_backwards(operands, target_result);
+    // Some of the solutions may have been missed because of the overflow
+    if search.overflow && !search.is_done() {
+        return Err(Overflow);
+    }
+    Ok(search.solutions)
+}
+
+pub fn format_expression<N: Num>(operands: &[N], solution: &[&dyn Operator<N>]) -> String {
+    let mut expression = operands[0].to_string();
+    for (operand, op) in operands[1..].iter().zip(solution) {
+        expression += &format!(" {} {operand}", op.symbol());
+    }
+    expression
+}
+
+struct SolutionSearch<'a, 'o, N> {
+    operators: &'o [&'a dyn Operator<N>],
+    max_solutions: usize,
+    // The operators chosen so far, the suffix is built backwards from the last operator and the
+    // prefix forwards from the first one
+    prefix: Vec<&'a dyn Operator<N>>,
+    suffix: Vec<&'a dyn Operator<N>>,
+    solutions: Vec<Vec<&'a dyn Operator<N>>>,
+    overflow: bool,
+}
+
+impl<N: Num> SolutionSearch<'_, '_, N> {
+    fn is_done(&self) -> bool {
+        self.solutions.len() >= self.max_solutions
+    }
+
+    // The same search as in `can_reach_target_result`
+    fn search_backwards(&mut self, operands: &[N], target_result: &N) {
+        let (last_operand, rest_operands) = operands.split_last().unwrap();
+        if rest_operands.is_empty() {
+            if last_operand == target_result {
+                self.push_solution();
+            }
+            return;
+        }
+        let operators = self.operators;
+        for &op in operators {
+            if self.is_done() {
+                return;
+            }
+            self.suffix.push(op);
+            match op.unapply(target_result, last_operand) {
+                Inverse::None => {}
+                Inverse::Unique(rest_target) => self.search_backwards(rest_operands, &rest_target),
+                Inverse::Any => {
+                    let (first_operand, rest_operands) = rest_operands.split_first().unwrap();
+                    self.search_forwards(first_operand, rest_operands);
+                }
+                Inverse::Overflow => self.overflow = true,
+            }
+            self.suffix.pop();
+        }
+    }
+
+    // Every operator sequence evaluating without an overflow is a solution
+    fn search_forwards(&mut self, acc: &N, operands: &[N]) {
+        let Some((operand, rest_operands)) = operands.split_first() else {
+            self.push_solution();
+            return;
+        };
+        let operators = self.operators;
+        for &op in operators {
+            if self.is_done() {
+                return;
+            }
+            match op.apply(acc, operand) {
+                Ok(Some(acc)) => {
+                    self.prefix.push(op);
+                    self.search_forwards(&acc, rest_operands);
+                    self.prefix.pop();
+                }
+                Ok(None) => {}
+                Err(Overflow) => self.overflow = true,
+            }
+        }
+    }
+
+    fn push_solution(&mut self) {
+        let solution = self.prefix.iter().chain(self.suffix.iter().rev()).copied().collect();
+        self.solutions.push(solution);
+    }
+}
+
+// The number of all operator sequences reaching the target result, without listing them, or
+// `Overflow` also if the number itself doesn't fit into `u128`
+pub fn count_solutions<N: Num>(operands: &[N], target_result: &N, operators: &[&dyn Operator<N>]) -> Result<u128, Overflow> {
+    let mut counter = SolutionCounter { operands, operators, backwards_memo: HashMap::new(), forwards_memo: HashMap::new() };
+    counter.count_backwards(operands.len() - 1, target_result)
+}
+
+struct SolutionCounter<'a, N> {
+    operands: &'a [N],
+    operators: &'a [&'a dyn Operator<N>],
+    // (index, remaining target) -> count, different operator choices often lead to the same
+    // remaining target (e.g. `+ 1` and `* 1`)
+    backwards_memo: HashMap<(usize, N), u128>,
+    // (index, accumulated result, last index) -> count
+    forwards_memo: HashMap<(usize, N, usize), u128>,
+}
+
+impl<N: Num> SolutionCounter<'_, N> {
+    // The number of ways the operands up to `index` reach `target_result`
+    fn count_backwards(&mut self, index: usize, target_result: &N) -> Result<u128, Overflow> {
+        if index == 0 {
+            return Ok((self.operands[0] == *target_result).into());
+        }
+        if let Some(&count) = self.backwards_memo.get(&(index, target_result.clone())) {
+            return Ok(count);
+        }
+        let mut count: u128 = 0;
+        for op in self.operators {
+            let op_count = match op.unapply(target_result, &self.operands[index]) {
+                Inverse::None => 0,
+                Inverse::Unique(rest_target) => self.count_backwards(index - 1, &rest_target)?,
+                Inverse::Any => self.count_forwards(0, &self.operands[0], index - 1)?,
+                Inverse::Overflow => return Err(Overflow),
+            };
+            count = count.checked_add(op_count).ok_or(Overflow)?;
+        }
+        self.backwards_memo.insert((index, target_result.clone()), count);
+        Ok(count)
+    }
+
+    // The number of ways to continue from `acc` at `index` up to `last_index` without an overflow
+    fn count_forwards(&mut self, index: usize, acc: &N, last_index: usize) -> Result<u128, Overflow> {
+        if index == last_index {
+            return Ok(1);
+        }
+        if let Some(&count) = self.forwards_memo.get(&(index, acc.clone(), last_index)) {
+            return Ok(count);
+        }
+        let mut count: u128 = 0;
+        for op in self.operators {
+            if let Some(acc) = op.apply(acc, &self.operands[index + 1])? {
+                let op_count = self.count_forwards(index + 1, &acc, last_index)?;
+                count = count.checked_add(op_count).ok_or(Overflow)?;
+            }
+        }
+        self.forwards_memo.insert((index, acc.clone(), last_index), count);
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operators::{Add, Concat, Mul, Sub};
+
+    fn expressions(operands: &[u64], target_result: u64, operators: &[&dyn Operator<u64>], max_solutions: usize) -> Vec<String> {
+        find_solutions(operands, &target_result, operators, max_solutions)
+            .unwrap()
+            .iter()
+            .map(|solution| format_expression(operands, solution))
+            .collect()
+    }
+
+    #[test]
+    fn solutions() {
+        let operators: [&dyn Operator<u64>; 3] = [&Add, &Mul, &Concat];
+        assert_eq!(expressions(&[11, 6, 16, 20], 292, &operators, usize::MAX), ["11 + 6 * 16 + 20"]);
+        assert_eq!(expressions(&[81, 40, 27], 3267, &operators, usize::MAX), ["81 * 40 + 27", "81 + 40 * 27"]);
+        assert_eq!(expressions(&[81, 40, 27], 3267, &operators, 1), ["81 * 40 + 27"]);
+        assert_eq!(expressions(&[6, 8, 6, 15], 7290, &operators, usize::MAX), ["6 * 8 || 6 * 15"]);
+        assert!(expressions(&[17, 5], 83, &operators, usize::MAX).is_empty());
+        // The operands before `* 0` can be combined in any way
+        assert_eq!(expressions(&[3, 4, 0], 0, &operators, usize::MAX), ["3 + 4 * 0", "3 * 4 * 0", "3 || 4 * 0"]);
+        // ... unless it overflows
+        assert_eq!(expressions(&[0, u64::MAX, 10, 0], 0, &operators, 1), ["0 * 18446744073709551615 * 10 + 0"]);
+        assert!(find_solutions(&[0, u64::MAX, 10, 0], &0, &operators, usize::MAX).is_err());
+    }
+
+    #[test]
+    fn counts() {
+        let operators: [&dyn Operator<u64>; 3] = [&Add, &Mul, &Concat];
+        assert_eq!(count_solutions(&[81, 40, 27], &3267, &operators), Ok(2));
+        assert_eq!(count_solutions(&[17, 5], &83, &operators), Ok(0));
+        assert_eq!(count_solutions(&[3, 4, 0], &0, &operators), Ok(3));
+        assert_eq!(count_solutions(&[0, u64::MAX, 10, 0], &0, &operators), Err(Overflow));
+        assert_eq!(count_solutions(&[0, u128::from(u64::MAX), 10, 0], &0, &[&Add, &Mul, &Concat]), Ok(11));
+        // `1 ? 1 ? ... ? 1 * 0` with `+` or `*` in each of the n - 1 gaps between n 1s
+        let ones_then_zero = |n| [vec![1_u64; n], vec![0]].concat();
+        assert_eq!(count_solutions(&ones_then_zero(128), &0, &[&Add, &Mul]), Ok(1 << 127));
+        assert_eq!(count_solutions(&ones_then_zero(140), &0, &[&Add, &Mul]), Err(Overflow));
+        // `+ 0`, `- 0`, `* 1` and `|| 1`
+        let operators: [&dyn Operator<u64>; 4] = [&Add, &Sub, &Mul, &Concat];
+        let operands = [1, 0, 1, 0, 1, 0, 1, 0];
+        for target_result in 0..200 {
+            let solutions = find_solutions(&operands, &target_result, &operators, usize::MAX).unwrap();
+            assert_eq!(count_solutions(&operands, &target_result, &operators), Ok(solutions.len() as u128));
+        }
+    }
+}
@@ -2,12 +2,178 @@
 //
 // SPDX-License-Identifier: MIT
 
-use std::io;
+use std::{
+    cmp::Reverse,
+    env, io,
+    time::{Duration, Instant},
+};
+
+use evaluation::{can_reach_target_result_with, Evaluation, EvaluationError};
+use expressions::{count_solutions, find_solutions, format_expression};
+use num::{Num, Overflow};
+#[cfg(feature = "bigint")]
+use num_bigint::BigUint;
+use operators::{Inverse, Operator};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+mod evaluation;
+mod expressions;
+mod num;
+mod operators;
 
 fn main() {
-    let input = read_input_from_stdin();
-    let res = solve(&input, Part::Two);
-    println!("{res}");
+    // [expressions [--all] | count | timings [--slowest <n>]] [--operators <name>,...]
+    //     [--num u64|u128|bigint] [--eval left-to-right|precedence|any-parens|any-order], where
+    //     the operator names are add, mul, concat, sub, xor and pow (or + * || - ^ **)
+    let mut mode = None;
+    let mut operator_names = None;
+    let mut evaluation = Evaluation::LeftToRight;
+    let mut num_type = None;
+    let mut max_solutions = 1;
+    let mut num_slowest = 10;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut option_value = || args.next().unwrap_or_else(|| panic!("missing value of {arg}"));
+        match arg.as_str() {
+            "--operators" => operator_names = Some(option_value()),
+            "--num" => num_type = Some(option_value()),
+            "--eval" => {
+                let name = option_value();
+                evaluation = Evaluation::from_name(&name).unwrap_or_else(|| panic!("unknown evaluation {name:?}"));
+            }
+            "--all" => max_solutions = usize::MAX,
+            "--slowest" => num_slowest = option_value().parse().unwrap(),
+            _ if arg.starts_with("--") => panic!("unknown option {arg:?}"),
+            _ if mode.is_none() => mode = Some(arg),
+            _ => panic!("unexpected argument {arg:?}"),
+        }
+    }
+
+    let lines: Vec<String> = io::stdin().lines().map(|l| l.unwrap()).collect();
+    // Without `--num`, the narrowest type that the input fits into, and if the sum overflows, the
+    // next wider ones (the other modes report overflows per equation instead)
+    let num_types: Vec<String> = match num_type {
+        Some(num_type) => vec![num_type],
+        None => num_types_from_narrowest(&lines).into_iter().map(|num_type| num_type.to_string()).collect(),
+    };
+    let mode = mode.as_deref();
+    if mode.is_some_and(|mode| mode != "timings") && evaluation != Evaluation::LeftToRight {
+        panic!("mode {mode:?} only supports left-to-right evaluation");
+    }
+    let operator_names = operator_names.as_deref();
+    for num_type in &num_types {
+        let res = match num_type.as_str() {
+            u64::NAME => run::<u64>(&lines, mode, operator_names, evaluation, max_solutions, num_slowest),
+            u128::NAME => run::<u128>(&lines, mode, operator_names, evaluation, max_solutions, num_slowest),
+            #[cfg(feature = "bigint")]
+            BigUint::NAME => run::<BigUint>(&lines, mode, operator_names, evaluation, max_solutions, num_slowest),
+            _ => panic!("unsupported number type {num_type:?} (bigint requires the \"bigint\" feature)"),
+        };
+        if res.is_ok() {
+            return;
+        }
+    }
+    let widest = num_types.last().unwrap();
+    if widest != NUM_TYPES.last().unwrap() {
+        panic!("overflow in {widest}, use a wider --num");
+    } else if cfg!(feature = "bigint") {
+        panic!("overflow in {widest}");
+    } else {
+        panic!("overflow in {widest}, wider numbers require the \"bigint\" feature");
+    }
+}
+
+// From the narrowest
+const NUM_TYPES: &[&str] = &[
+    u64::NAME,
+    u128::NAME,
+    #[cfg(feature = "bigint")]
+    BigUint::NAME,
+];
+
+fn num_types_from_narrowest(lines: &[String]) -> Vec<&'static str> {
+    let narrowest = narrowest_num_type(lines).unwrap_or_else(|| {
+        panic!("the input doesn't fit into {}, wider numbers require the \"bigint\" feature", u128::NAME)
+    });
+    NUM_TYPES.iter().copied().skip_while(|&num_type| num_type != narrowest).collect()
+}
+
+// `None` if the input doesn't fit into any supported type
+fn narrowest_num_type(lines: &[String]) -> Option<&'static str> {
+    if parse_input::<u64>(lines).is_some() {
+        Some(u64::NAME)
+    } else if parse_input::<u128>(lines).is_some() {
+        Some(u128::NAME)
+    } else if cfg!(feature = "bigint") {
+        // Any input fits
+        NUM_TYPES.last().copied()
+    } else {
+        None
+    }
+}
+
+fn run<N: Num>(
+    lines: &[String],
+    mode: Option<&str>,
+    operator_names: Option<&str>,
+    evaluation: Evaluation,
+    max_solutions: usize,
+    num_slowest: usize,
+) -> Result<(), Overflow> {
+    let input = parse_input::<N>(lines).unwrap_or_else(|| panic!("the input doesn't fit into {}", N::NAME));
+    let operators = match operator_names {
+        Some(names) => names
+            .split(',')
+            .map(|name| operators::from_name(name).unwrap_or_else(|| panic!("unknown operator {name:?}")))
+            .collect(),
+        None => Part::Two.operators(),
+    };
+    let overflow_msg = format!("overflow in {}, use a wider --num", N::NAME);
+    match mode {
+        None => {
+            let (res, unevaluated) = solve(&input, &operators, evaluation)?;
+            for i in unevaluated {
+                eprintln!("{}: {}, skipped", input[i].target_result, EvaluationError::TooManyOperands);
+            }
+            println!("{res}");
+        }
+        Some("expressions") => {
+            for equ in &input {
+                match find_solutions(&equ.operands, &equ.target_result, &operators, max_solutions) {
+                    Ok(solutions) => {
+                        for solution in solutions {
+                            println!("{}: {}", equ.target_result, format_expression(&equ.operands, &solution));
+                        }
+                    }
+                    Err(Overflow) => println!("{}: {overflow_msg}", equ.target_result),
+                }
+            }
+        }
+        Some("count") => {
+            for equ in &input {
+                match count_solutions(&equ.operands, &equ.target_result, &operators) {
+                    Ok(count) => println!("{}: {count}", equ.target_result),
+                    Err(Overflow) => println!("{}: {overflow_msg}", equ.target_result),
+                }
+            }
+        }
+        Some("timings") => {
+            let mut timed: Vec<_> = input.iter().zip(evaluate_equations(&input, &operators, evaluation)).collect();
+            timed.sort_by_key(|(_, (_, duration))| Reverse(*duration));
+            for (equ, (res, duration)) in timed.into_iter().take(num_slowest) {
+                let operands: Vec<String> = equ.operands.iter().map(|operand| operand.to_string()).collect();
+                let status = match res {
+                    Ok(true) => "solvable".to_string(),
+                    Ok(false) => "unsolvable".to_string(),
+                    Err(err) => err.to_string(),
+                };
+                println!("{duration:>12.3?}  {}: {}  {status}", equ.target_result, operands.join(" "));
+            }
+        }
+        Some(mode) => panic!("unknown mode {mode:?}, expected \"expressions\", \"count\" or \"timings\""),
+    }
+    Ok(())
 }
 
 #[derive(Clone, Copy)]
@@ -17,77 +183,124 @@ enum Part {
 }
 
 impl Part {
-    fn is_concat_allowed(self) -> bool {
+    fn operators<N: Num>(self) -> Vec<&'static dyn Operator<N>> {
         match self {
-            Part::One => false,
-            Part::Two => true,
+            Part::One => vec![&operators::Add, &operators::Mul],
+            Part::Two => vec![&operators::Add, &operators::Mul, &operators::Concat],
         }
     }
 }
 
-fn solve(input: &[InputEquation], part: Part) -> u64 {
-    input
-        .iter()
-        .filter(|&equ| can_reach_target_result(&equ.operands, equ.target_result, part.is_concat_allowed()))
-        .map(|equ| equ.target_result)
-        .sum()
+// The sum of the solvable equations and the indices of the equations with too many operands to
+// evaluate, which are left out of the sum
+fn solve<N: Num>(
+    input: &[InputEquation<N>],
+    operators: &[&dyn Operator<N>],
+    evaluation: Evaluation,
+) -> Result<(N, Vec<usize>), Overflow> {
+    let mut sum = N::from_u32(0);
+    let mut unevaluated = vec![];
+    for (i, (equ, (res, _))) in input.iter().zip(evaluate_equations(input, operators, evaluation)).enumerate() {
+        match res {
+            Ok(true) => sum = sum.checked_add(&equ.target_result).ok_or(Overflow)?,
+            Ok(false) => {}
+            Err(EvaluationError::Overflow) => return Err(Overflow),
+            Err(EvaluationError::TooManyOperands) => unevaluated.push(i),
+        }
+    }
+    Ok((sum, unevaluated))
 }
 
-fn can_reach_target_result(operands: &[u64], target_result: u64, is_concat_allowed: bool) -> bool {
-    let (&last_operand, rest_operands) = operands.split_last().unwrap();
+// Whether each equation can be solved and how long it took, in the order of the input
+fn evaluate_equations<N: Num>(
+    input: &[InputEquation<N>],
+    operators: &[&dyn Operator<N>],
+    evaluation: Evaluation,
+) -> Vec<(Result<bool, EvaluationError>, Duration)> {
+    #[cfg(feature = "rayon")]
+    let equations = input.par_iter();
+    #[cfg(not(feature = "rayon"))]
+    let equations = input.iter();
+    equations
+        .map(|equ| {
+            let start = Instant::now();
+            let res = can_reach_target_result_with(&equ.operands, &equ.target_result, operators, evaluation);
+            (res, start.elapsed())
+        })
+        .collect()
+}
+
+// Works from the last operand backwards, because only the operators that can be unapplied from
+// the target result need to be tried
+fn can_reach_target_result<N: Num>(operands: &[N], target_result: &N, operators: &[&dyn Operator<N>]) -> Result<bool, Overflow> {
+    let (last_operand, rest_operands) = operands.split_last().unwrap();
     if rest_operands.is_empty() {
-        return last_operand == target_result;
+        return Ok(last_operand == target_result);
     }
-    if let Some(addition_rest) = target_result.checked_sub(last_operand) {
-        if can_reach_target_result(rest_operands, addition_rest, is_concat_allowed) {
-            return true;
+    // An overflow only matters if the target can't be reached in another way
+    let mut res = Ok(false);
+    for op in operators {
+        let is_reached = match op.unapply(target_result, last_operand) {
+            Inverse::None => Ok(false),
+            Inverse::Unique(rest_target) => can_reach_target_result(rest_operands, &rest_target, operators),
+            Inverse::Any => can_evaluate(rest_operands, operators),
+            Inverse::Overflow => Err(Overflow),
+        };
+        match is_reached {
+            Ok(true) => return Ok(true),
+            Ok(false) => {}
+            Err(Overflow) => res = Err(Overflow),
         }
     }
-    if let Some(multiplication_rest) = target_result.checked_div(last_operand) {
-        if target_result % last_operand == 0
-            && can_reach_target_result(rest_operands, multiplication_rest, is_concat_allowed)
-        {
-            return true;
-        }
-    }
-    if is_concat_allowed {
-        let nearest_10_pow = nearest_higher_power_of_10(last_operand);
-        if target_result % nearest_10_pow == last_operand {
-            let concat_rest = target_result / nearest_10_pow;
-            if can_reach_target_result(rest_operands, concat_rest, is_concat_allowed) {
-                return true;
+    res
+}
+
+// Whether any choice of operators evaluates to a non-negative result
+fn can_evaluate<N: Num>(operands: &[N], operators: &[&dyn Operator<N>]) -> Result<bool, Overflow> {
+    fn can_evaluate_rest<N: Num>(acc: &N, operands: &[N], operators: &[&dyn Operator<N>]) -> Result<bool, Overflow> {
+        let Some((operand, rest_operands)) = operands.split_first() else {
+            return Ok(true);
+        };
+        let mut res = Ok(false);
+        for op in operators {
+            let is_evaluated = match op.apply(acc, operand) {
+                Ok(Some(acc)) => can_evaluate_rest(&acc, rest_operands, operators),
+                Ok(None) => Ok(false),
+                Err(Overflow) => Err(Overflow),
+            };
+            match is_evaluated {
+                Ok(true) => return Ok(true),
+                Ok(false) => {}
+                Err(Overflow) => res = Err(Overflow),
             }
         }
+        res
     }
-    false
-}
-
-fn nearest_higher_power_of_10(x: u64) -> u64 {
-    let x_log10 = x.checked_ilog10().unwrap_or(0);
-    10_u64.pow(x_log10 + 1)
+    let (first_operand, rest_operands) = operands.split_first().unwrap();
+    can_evaluate_rest(first_operand, rest_operands, operators)
 }
 
 #[derive(Debug)]
-struct InputEquation {
-    target_result: u64,
-    operands: Vec<u64>,
+struct InputEquation<N> {
+    target_result: N,
+    operands: Vec<N>,
 }
 
-fn read_input_from_stdin() -> Vec<InputEquation> {
-    io::stdin()
-        .lines()
-        .map(|l| l.unwrap())
+// `None` if any number doesn't fit into `N`
+fn parse_input<N: Num>(lines: &[String]) -> Option<Vec<InputEquation<N>>> {
+    lines
+        .iter()
         .map(|line| {
             let (target_result, operands) = line.split_once(": ").unwrap();
-            let target_result = target_result.parse::<u64>().unwrap();
-            let operands: Vec<u64> = operands
+            let target_result = target_result.parse::<N>().ok()?;
+            let operands: Vec<N> = operands
                 .split_whitespace()
-                .map(|s| s.parse().unwrap())
-                .collect();
-            InputEquation {
+                .map(|s| s.parse().ok())
+                .collect::<Option<_>>()?;
+            Some(InputEquation {
                 target_result,
                 operands,
-            }
+            })
         })
         .collect()
 }
@@ -95,12 +308,89 @@ fn read_input_from_stdin() -> Vec<InputEquation> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use evaluation::MAX_REORDERED_OPERANDS;
+
+    fn example() -> Vec<InputEquation<u64>> {
+        [
+            (190, vec![10, 19]),
+            (3267, vec![81, 40, 27]),
+            (83, vec![17, 5]),
+            (156, vec![15, 6]),
+            (7290, vec![6, 8, 6, 15]),
+            (161011, vec![16, 10, 13]),
+            (192, vec![17, 8, 14]),
+            (21037, vec![9, 7, 18, 13]),
+            (292, vec![11, 6, 16, 20]),
+        ]
+        .into_iter()
+        .map(|(target_result, operands)| InputEquation { target_result, operands })
+        .collect()
+    }
+
+    #[test]
+    fn example_parts() {
+        assert_eq!(solve(&example(), &Part::One.operators(), Evaluation::LeftToRight), Ok((3749, vec![])));
+        assert_eq!(solve(&example(), &Part::Two.operators(), Evaluation::LeftToRight), Ok((11387, vec![])));
+    }
+
+    #[test]
+    fn evaluated_in_order() {
+        let input = example();
+        let operators = Part::Two.operators();
+        let results: Vec<Result<bool, EvaluationError>> = evaluate_equations(&input, &operators, Evaluation::LeftToRight)
+            .into_iter()
+            .map(|(res, _)| res)
+            .collect();
+        let expected: Vec<Result<bool, EvaluationError>> = input
+            .iter()
+            .map(|equ| can_reach_target_result(&equ.operands, &equ.target_result, &operators).map_err(EvaluationError::from))
+            .collect();
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    fn too_many_operands() {
+        let mut input = example();
+        input.push(InputEquation { target_result: 9, operands: vec![1; MAX_REORDERED_OPERANDS + 1] });
+        assert_eq!(solve(&input, &Part::Two.operators(), Evaluation::AnyOrder), Ok((11387, vec![9])));
+    }
+
+    #[test]
+    fn other_operators() {
+        let operators: Vec<&dyn Operator<u64>> = vec![&operators::Sub, &operators::Xor, &operators::Pow];
+        // 2 ** 5 - 7 ^ 1
+        assert_eq!(can_reach_target_result(&[2, 5, 7, 1], &24, &operators), Ok(true));
+        assert_eq!(can_reach_target_result(&[2, 5, 7, 1], &23, &operators), Ok(false));
+        // 0 can be reached as `... * 0` no matter what comes before, unless it overflows
+        assert_eq!(can_reach_target_result(&[3_u64, 4, 0], &0, &[&operators::Mul]), Ok(true));
+        assert_eq!(can_reach_target_result(&[u64::MAX, 2, 0], &0, &[&operators::Mul]), Err(Overflow));
+        assert_eq!(can_reach_target_result(&[u128::from(u64::MAX), 2, 0], &0, &[&operators::Mul]), Ok(true));
+    }
+
+    #[test]
+    fn num_types() {
+        let lines = |line: &str, n| vec![line.to_string(); n];
+        assert_eq!(narrowest_num_type(&lines("18446744073709551615: 18446744073709551614 1", 1)), Some("u64"));
+        assert_eq!(narrowest_num_type(&lines("18446744073709551616: 18446744073709551615 1", 1)), Some("u128"));
+        let bigint = if cfg!(feature = "bigint") { Some("bigint") } else { None };
+        assert_eq!(narrowest_num_type(&lines("1: 1 340282366920938463463374607431768211456", 1)), bigint);
+        let wider_than_u64 = NUM_TYPES[1..].to_vec();
+        assert_eq!(num_types_from_narrowest(&lines("18446744073709551616: 1", 1)), wider_than_u64);
+        // The sum of the results doesn't fit, even though each result does, so `u128` is tried next
+        assert_eq!(num_types_from_narrowest(&lines("18446744073709551615: 18446744073709551614 1", 2))[..2], ["u64", "u128"]);
+        let input = parse_input::<u64>(&lines("18446744073709551615: 18446744073709551614 1", 2)).unwrap();
+        assert_eq!(solve(&input, &Part::One.operators(), Evaluation::LeftToRight), Err(Overflow));
+        let input = parse_input::<u128>(&lines("18446744073709551615: 18446744073709551614 1", 2)).unwrap();
+        assert_eq!(solve(&input, &Part::One.operators(), Evaluation::LeftToRight), Ok((2 * u128::from(u64::MAX), vec![])));
+    }
 
+    #[cfg(feature = "bigint")]
     #[test]
-    fn nearest_power_of_10() {
-        assert_eq!(nearest_higher_power_of_10(0), 10);
-        assert_eq!(nearest_higher_power_of_10(1), 10);
-        assert_eq!(nearest_higher_power_of_10(9), 10);
-        assert_eq!(nearest_higher_power_of_10(10), 100);
+    fn bigint() {
+        let operand = "12345678901234567890";
+        let target_result = operand.repeat(3);
+        let input = parse_input::<BigUint>(&[format!("{target_result}: {operand} {operand} {operand}")]).unwrap();
+        assert_eq!(narrowest_num_type(&[format!("{target_result}: 1")]), Some("bigint"));
+        assert_eq!(solve(&input, &Part::Two.operators(), Evaluation::LeftToRight), Ok((target_result.parse().unwrap(), vec![])));
     }
 }
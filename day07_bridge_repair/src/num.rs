@@ -0,0 +1,158 @@
+// SPDX-FileCopyrightText: 2024 Petr Pucil <petr.pucil@seznam.cz>
+//
+// SPDX-License-Identifier: MIT
+
+use std::{
+    fmt::{Debug, Display},
+    hash::Hash,
+    str::FromStr,
+};
+
+#[cfg(feature = "bigint")]
+use num_bigint::BigUint;
+
+// An unsigned integer type to solve the equations in. `None` from the checked operations means
+// that the result doesn't fit into the type, except for `checked_sub`, where it means that the
+// result would be negative.
+pub trait Num: Clone + Eq + Ord + Hash + Debug + Display + FromStr + Send + Sync + 'static {
+    const NAME: &'static str;
+
+    fn from_u32(x: u32) -> Self;
+
+    fn checked_add(&self, rhs: &Self) -> Option<Self>;
+
+    fn checked_sub(&self, rhs: &Self) -> Option<Self>;
+
+    fn checked_mul(&self, rhs: &Self) -> Option<Self>;
+
+    // Panics if `rhs` is zero
+    fn div_rem(&self, rhs: &Self) -> (Self, Self);
+
+    fn xor(&self, rhs: &Self) -> Self;
+
+    fn checked_pow(&self, exp: u32) -> Option<Self>;
+
+    fn to_u32(&self) -> Option<u32>;
+
+    fn num_digits(&self) -> u32;
+}
+
+// Reported instead of wrapping around when a value needed by the solver doesn't fit into the type
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Overflow;
+
+macro_rules! impl_num_for_primitive {
+    ($($t:ty),*) => {$(
+        impl Num for $t {
+            const NAME: &'static str = stringify!($t);
+
+            fn from_u32(x: u32) -> Self {
+                x.into()
+            }
+
+            fn checked_add(&self, rhs: &Self) -> Option<Self> {
+                <$t>::checked_add(*self, *rhs)
+            }
+
+            fn checked_sub(&self, rhs: &Self) -> Option<Self> {
+                <$t>::checked_sub(*self, *rhs)
+            }
+
+            fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+                <$t>::checked_mul(*self, *rhs)
+            }
+
+            fn div_rem(&self, rhs: &Self) -> (Self, Self) {
+                (self / rhs, self % rhs)
+            }
+
+            fn xor(&self, rhs: &Self) -> Self {
+                self ^ rhs
+            }
+
+            fn checked_pow(&self, exp: u32) -> Option<Self> {
+                <$t>::checked_pow(*self, exp)
+            }
+
+            fn to_u32(&self) -> Option<u32> {
+                (*self).try_into().ok()
+            }
+
+            fn num_digits(&self) -> u32 {
+                self.checked_ilog10().unwrap_or(0) + 1
+            }
+        }
+    )*};
+}
+
+impl_num_for_primitive!(u64, u128);
+
+// Powers with results longer than this are considered an overflow, because computing them would
+// take too long
+#[cfg(feature = "bigint")]
+const MAX_BIGINT_POW_BITS: u64 = 1 << 20;
+
+#[cfg(feature = "bigint")]
+impl Num for BigUint {
+    const NAME: &'static str = "bigint";
+
+    fn from_u32(x: u32) -> Self {
+        x.into()
+    }
+
+    fn checked_add(&self, rhs: &Self) -> Option<Self> {
+        Some(self + rhs)
+    }
+
+    fn checked_sub(&self, rhs: &Self) -> Option<Self> {
+        (self >= rhs).then(|| self - rhs)
+    }
+
+    fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+        Some(self * rhs)
+    }
+
+    fn div_rem(&self, rhs: &Self) -> (Self, Self) {
+        (self / rhs, self % rhs)
+    }
+
+    fn xor(&self, rhs: &Self) -> Self {
+        self ^ rhs
+    }
+
+    fn checked_pow(&self, exp: u32) -> Option<Self> {
+        (self.bits() * u64::from(exp) <= MAX_BIGINT_POW_BITS).then(|| self.pow(exp))
+    }
+
+    fn to_u32(&self) -> Option<u32> {
+        self.try_into().ok()
+    }
+
+    fn num_digits(&self) -> u32 {
+        self.to_string().len().try_into().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primitives() {
+        assert_eq!(Num::checked_add(&u64::MAX, &1), None);
+        assert_eq!(Num::checked_add(&u128::from(u64::MAX), &1), Some(1 << 64));
+        assert_eq!(Num::checked_sub(&3_u64, &4), None);
+        assert_eq!(0_u64.num_digits(), 1);
+        assert_eq!(u128::MAX.num_digits(), 39);
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn bigint() {
+        let x: BigUint = "123456789012345678901234567890123456789012".parse().unwrap();
+        assert_eq!(x.num_digits(), 42);
+        assert_eq!(Num::checked_sub(&BigUint::from(3_u32), &BigUint::from(4_u32)), None);
+        assert_eq!(BigUint::from(2_u32).checked_pow(200).unwrap().bits(), 201);
+        assert_eq!(BigUint::from(2_u32).checked_pow(u32::MAX), None);
+    }
+}
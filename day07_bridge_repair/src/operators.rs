@@ -0,0 +1,296 @@
+// SPDX-FileCopyrightText: 2024 Petr Pucil <petr.pucil@seznam.cz>
+//
+// SPDX-License-Identifier: MIT
+
+use crate::num::{Num, Overflow};
+
+// The left operand that an operator must have been applied to in order to give a known result
+#[derive(Debug, PartialEq, Eq)]
+pub enum Inverse<N> {
+    None,
+    Unique(N),
+    // Any left operand gives the result (e.g. `x * 0 == 0`)
+    Any,
+    // There is a left operand, but it doesn't fit into `N`
+    Overflow,
+}
+
+pub trait Operator<N>: Sync {
+    fn symbol(&self) -> &'static str;
+
+    // Only used when evaluating with precedence: from `^` (lowest) over `+ -`, `*` and `**` to
+    // `||`, which joins the digits of its operands into a single number
+    fn precedence(&self) -> u8;
+
+    fn is_right_associative(&self) -> bool {
+        false
+    }
+
+    // Whether the result is at least as large as both operands when neither of them is 0, so that
+    // values above the target can't lead to it
+    fn never_decreases(&self) -> bool {
+        false
+    }
+
+    // `Ok(None)` if the result is negative
+    fn apply(&self, lhs: &N, rhs: &N) -> Result<Option<N>, Overflow>;
+
+    // Which `lhs` satisfies `apply(lhs, rhs) == Ok(Some(result))`
+    fn unapply(&self, result: &N, rhs: &N) -> Inverse<N>;
+}
+
+pub fn all<N: Num>() -> [&'static dyn Operator<N>; 6] {
+    [&Add, &Mul, &Concat, &Sub, &Xor, &Pow]
+}
+
+// The operator can also be given by its symbol
+pub fn from_name<N: Num>(name: &str) -> Option<&'static dyn Operator<N>> {
+    match name {
+        "add" => Some(&Add),
+        "mul" => Some(&Mul),
+        "concat" => Some(&Concat),
+        "sub" => Some(&Sub),
+        "xor" => Some(&Xor),
+        "pow" => Some(&Pow),
+        _ => all().into_iter().find(|op| op.symbol() == name),
+    }
+}
+
+pub struct Add;
+pub struct Mul;
+pub struct Concat;
+pub struct Sub;
+pub struct Xor;
+pub struct Pow;
+
+impl<N: Num> Operator<N> for Add {
+    fn symbol(&self) -> &'static str {
+        "+"
+    }
+
+    fn precedence(&self) -> u8 {
+        1
+    }
+
+    fn never_decreases(&self) -> bool {
+        true
+    }
+
+    fn apply(&self, lhs: &N, rhs: &N) -> Result<Option<N>, Overflow> {
+        lhs.checked_add(rhs).ok_or(Overflow).map(Some)
+    }
+
+    fn unapply(&self, result: &N, rhs: &N) -> Inverse<N> {
+        result.checked_sub(rhs).map_or(Inverse::None, Inverse::Unique)
+    }
+}
+
+impl<N: Num> Operator<N> for Mul {
+    fn symbol(&self) -> &'static str {
+        "*"
+    }
+
+    fn precedence(&self) -> u8 {
+        2
+    }
+
+    fn never_decreases(&self) -> bool {
+        true
+    }
+
+    fn apply(&self, lhs: &N, rhs: &N) -> Result<Option<N>, Overflow> {
+        lhs.checked_mul(rhs).ok_or(Overflow).map(Some)
+    }
+
+    fn unapply(&self, result: &N, rhs: &N) -> Inverse<N> {
+        let zero = N::from_u32(0);
+        if *rhs == zero {
+            return if *result == zero { Inverse::Any } else { Inverse::None };
+        }
+        match result.div_rem(rhs) {
+            (quotient, remainder) if remainder == zero => Inverse::Unique(quotient),
+            _ => Inverse::None,
+        }
+    }
+}
+
+impl<N: Num> Operator<N> for Concat {
+    fn symbol(&self) -> &'static str {
+        "||"
+    }
+
+    fn precedence(&self) -> u8 {
+        4
+    }
+
+    fn never_decreases(&self) -> bool {
+        true
+    }
+
+    fn apply(&self, lhs: &N, rhs: &N) -> Result<Option<N>, Overflow> {
+        if *lhs == N::from_u32(0) {
+            return Ok(Some(rhs.clone()));
+        }
+        let pow = nearest_higher_power_of_10(rhs).ok_or(Overflow)?;
+        lhs.checked_mul(&pow).and_then(|shifted| shifted.checked_add(rhs)).ok_or(Overflow).map(Some)
+    }
+
+    fn unapply(&self, result: &N, rhs: &N) -> Inverse<N> {
+        match nearest_higher_power_of_10(rhs) {
+            Some(pow) => match result.div_rem(&pow) {
+                (quotient, remainder) if remainder == *rhs => Inverse::Unique(quotient),
+                _ => Inverse::None,
+            },
+            // If the power of 10 doesn't fit into `N`, neither does any concatenation with `rhs`
+            // other than `0 || rhs == rhs`
+            None if result == rhs => Inverse::Unique(N::from_u32(0)),
+            None => Inverse::None,
+        }
+    }
+}
+
+// Negative results are undefined, so the left operand must be at least the right one
+impl<N: Num> Operator<N> for Sub {
+    fn symbol(&self) -> &'static str {
+        "-"
+    }
+
+    fn precedence(&self) -> u8 {
+        1
+    }
+
+    fn apply(&self, lhs: &N, rhs: &N) -> Result<Option<N>, Overflow> {
+        Ok(lhs.checked_sub(rhs))
+    }
+
+    fn unapply(&self, result: &N, rhs: &N) -> Inverse<N> {
+        result.checked_add(rhs).map_or(Inverse::Overflow, Inverse::Unique)
+    }
+}
+
+impl<N: Num> Operator<N> for Xor {
+    fn symbol(&self) -> &'static str {
+        "^"
+    }
+
+    fn precedence(&self) -> u8 {
+        0
+    }
+
+    fn apply(&self, lhs: &N, rhs: &N) -> Result<Option<N>, Overflow> {
+        Ok(Some(lhs.xor(rhs)))
+    }
+
+    fn unapply(&self, result: &N, rhs: &N) -> Inverse<N> {
+        Inverse::Unique(result.xor(rhs))
+    }
+}
+
+impl<N: Num> Operator<N> for Pow {
+    fn symbol(&self) -> &'static str {
+        "**"
+    }
+
+    fn precedence(&self) -> u8 {
+        3
+    }
+
+    fn is_right_associative(&self) -> bool {
+        true
+    }
+
+    fn apply(&self, lhs: &N, rhs: &N) -> Result<Option<N>, Overflow> {
+        let (zero, one) = (N::from_u32(0), N::from_u32(1));
+        if *lhs == zero || *lhs == one {
+            // Exponents not fitting into `u32` would overflow for any other base
+            return Ok(Some(if *rhs == zero { one } else { lhs.clone() }));
+        }
+        let exp = rhs.to_u32().ok_or(Overflow)?;
+        lhs.checked_pow(exp).ok_or(Overflow).map(Some)
+    }
+
+    fn unapply(&self, result: &N, rhs: &N) -> Inverse<N> {
+        let (zero, one, two) = (N::from_u32(0), N::from_u32(1), N::from_u32(2));
+        if *rhs == zero {
+            return if *result == one { Inverse::Any } else { Inverse::None };
+        }
+        // Binary search for the `rhs`-th root, `apply` is increasing in `lhs`
+        let (mut lo, mut hi) = (zero, result.clone());
+        while lo < hi {
+            let mid = hi.checked_sub(&lo).unwrap().div_rem(&two).0.checked_add(&lo).unwrap();
+            match self.apply(&mid, rhs) {
+                Ok(Some(pow)) if pow < *result => lo = mid.checked_add(&one).unwrap(),
+                _ => hi = mid,
+            }
+        }
+        if self.apply(&lo, rhs) == Ok(Some(result.clone())) {
+            Inverse::Unique(lo)
+        } else {
+            Inverse::None
+        }
+    }
+}
+
+// `None` if the power doesn't fit into `N`
+fn nearest_higher_power_of_10<N: Num>(x: &N) -> Option<N> {
+    N::from_u32(10).checked_pow(x.num_digits())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_power_of_10() {
+        assert_eq!(nearest_higher_power_of_10(&0_u64), Some(10));
+        assert_eq!(nearest_higher_power_of_10(&1_u64), Some(10));
+        assert_eq!(nearest_higher_power_of_10(&9_u64), Some(10));
+        assert_eq!(nearest_higher_power_of_10(&10_u64), Some(100));
+        assert_eq!(nearest_higher_power_of_10(&u64::MAX), None);
+        assert_eq!(nearest_higher_power_of_10(&u128::from(u64::MAX)), Some(10_u128.pow(20)));
+    }
+
+    #[test]
+    fn apply_and_unapply() {
+        assert_eq!(from_name::<u64>("||").unwrap().symbol(), "||");
+        assert!(from_name::<u64>("/").is_none());
+        for op in all::<u64>() {
+            for lhs in 0..30 {
+                for rhs in 0..12 {
+                    let Ok(Some(result)) = op.apply(&lhs, &rhs) else {
+                        continue;
+                    };
+                    match op.unapply(&result, &rhs) {
+                        Inverse::Unique(unapplied) => assert_eq!(unapplied, lhs, "{lhs} {} {rhs}", op.symbol()),
+                        Inverse::Any => {}
+                        _ => panic!("{lhs} {} {rhs} can't be unapplied", op.symbol()),
+                    }
+                }
+            }
+        }
+        assert_eq!(Concat.apply(&12_u64, &345), Ok(Some(12345)));
+        assert_eq!(Concat.apply(&2_u64, &u64::MAX), Err(Overflow));
+        assert_eq!(Concat.unapply(&12345_u64, &45), Inverse::Unique(123));
+        assert_eq!(Concat.unapply(&12345_u64, &46), Inverse::None);
+        assert_eq!(Mul.unapply(&0_u64, &0), Inverse::Any);
+        assert_eq!(Pow.unapply(&(1_u64 << 40), &4), Inverse::Unique(1024));
+        assert_eq!(Pow.unapply(&((1_u64 << 40) + 1), &4), Inverse::None);
+        assert_eq!(Pow.apply(&2_u64, &64), Err(Overflow));
+        assert_eq!(Sub.apply(&3_u64, &4), Ok(None));
+        assert_eq!(Sub.unapply(&3_u64, &u64::MAX), Inverse::Overflow);
+    }
+
+    #[test]
+    fn never_decreasing() {
+        for op in all::<u64>().into_iter().filter(|op| op.never_decreases()) {
+            for lhs in 1..30 {
+                for rhs in 1..30 {
+                    let result = op.apply(&lhs, &rhs).unwrap().unwrap();
+                    assert!(result >= lhs && result >= rhs, "{lhs} {} {rhs}", op.symbol());
+                }
+            }
+        }
+        // 1 ** 2 < 2
+        assert!(!Operator::<u64>::never_decreases(&Pow));
+    }
+}
@@ -0,0 +1,86 @@
+// SPDX-FileCopyrightText: 2024 Petr Pucil <petr.pucil@seznam.cz>
+//
+// SPDX-License-Identifier: MIT
+
+use std::collections::HashMap;
+
+// Which cells of the map are antennas, each byte being a frequency
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum AntennaClass {
+    // ASCII letters and digits, as in the puzzle
+    #[default]
+    Alphanumeric,
+    // Anything but `.`, `#`, whitespace (e.g. the `\r` of CRLF line endings) and control bytes
+    Any,
+    Set(Vec<u8>),
+}
+
+impl AntennaClass {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "alnum" => Some(AntennaClass::Alphanumeric),
+            "any" => Some(AntennaClass::Any),
+            _ => None,
+        }
+    }
+
+    pub fn is_antenna(&self, c: u8) -> bool {
+        match self {
+            AntennaClass::Alphanumeric => c.is_ascii_alphanumeric(),
+            AntennaClass::Any => c != b'.' && c != b'#' && !c.is_ascii_whitespace() && !c.is_ascii_control(),
+            AntennaClass::Set(set) => set.contains(&c),
+        }
+    }
+}
+
+// Names of the frequencies, for inputs with more frequencies than single characters can tell apart
+// nicely. Each line of a legend is a frequency byte, whitespace and its label, e.g. `A 2.4GHz`.
+#[derive(Debug, Default)]
+pub struct Legend {
+    labels: HashMap<u8, String>,
+}
+
+impl Legend {
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut labels = HashMap::new();
+        for line in bytes.split(|&b| b == b'\n').map(|line| line.strip_suffix(b"\r").unwrap_or(line)) {
+            let Some((&freq, rest)) = line.split_first() else {
+                continue;
+            };
+            let label = String::from_utf8_lossy(rest);
+            assert!(
+                label.starts_with(char::is_whitespace),
+                "legend line {:?} isn't in the form <frequency> <label>",
+                String::from_utf8_lossy(line)
+            );
+            let prev = labels.insert(freq, label.trim().to_string());
+            assert!(prev.is_none(), "frequency {:?} is in the legend twice", char::from(freq));
+        }
+        Self { labels }
+    }
+
+    // Frequencies missing from the legend are labeled by themselves
+    pub fn label(&self, freq: u8) -> String {
+        self.labels.get(&freq).cloned().unwrap_or_else(|| char::from(freq).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legend() {
+        let legend = Legend::from_bytes(b"a 2.4GHz\r\n\xff  channel 255\n\n");
+        assert_eq!(legend.label(b'a'), "2.4GHz");
+        assert_eq!(legend.label(0xff), "channel 255");
+        assert_eq!(legend.label(b'b'), "b");
+        assert!(AntennaClass::Any.is_antenna(0xff));
+        assert!(!AntennaClass::Alphanumeric.is_antenna(0xff));
+        assert!(!AntennaClass::Any.is_antenna(b'#'));
+        for c in [b'\r', b' ', b'\t', 0, 0x7f] {
+            assert!(!AntennaClass::Any.is_antenna(c));
+        }
+        assert!(AntennaClass::Set(b"@".to_vec()).is_antenna(b'@'));
+    }
+}
@@ -0,0 +1,50 @@
+// SPDX-FileCopyrightText: 2024 Petr Pucil <petr.pucil@seznam.cz>
+//
+// SPDX-License-Identifier: MIT
+
+use utils::{Pos, Table};
+
+use crate::antennas::AntennaClass;
+
+// The antinodes within a table, kept apart from the table so that antennas aren't overwritten
+pub struct AntinodeSet {
+    cols: usize,
+    is_antinode: Vec<bool>,
+    len: usize,
+}
+
+impl AntinodeSet {
+    pub fn new(table: &Table) -> Self {
+        Self { cols: table.cols(), is_antinode: vec![false; table.rows() * table.cols()], len: 0 }
+    }
+
+    // Whether the antinode is new
+    pub fn insert(&mut self, pos: Pos) -> bool {
+        let is_antinode = &mut self.is_antinode[pos.r() * self.cols + pos.c()];
+        if *is_antinode {
+            return false;
+        }
+        *is_antinode = true;
+        self.len += 1;
+        true
+    }
+
+    pub fn contains(&self, pos: Pos) -> bool {
+        self.is_antinode[pos.r() * self.cols + pos.c()]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    // The table with antinodes marked as `#`, except where they coincide with antennas
+    pub fn overlay(&self, table: &Table, antennas: &AntennaClass) -> Table {
+        let mut overlaid = table.clone();
+        for pos in table.all_positions() {
+            if self.contains(pos) && !antennas.is_antenna(table[pos]) {
+                overlaid[pos] = b'#';
+            }
+        }
+        overlaid
+    }
+}
@@ -0,0 +1,60 @@
+// SPDX-FileCopyrightText: 2024 Petr Pucil <petr.pucil@seznam.cz>
+//
+// SPDX-License-Identifier: MIT
+
+use utils::{Pos, Table};
+
+// (row, column), possibly outside the table
+pub type Point = (isize, isize);
+
+// The region of the plane where antinodes are looked for. It must contain the antennas and be
+// convex so that walking along a line from an antenna can stop once it leaves the region.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bounds {
+    // Inclusive
+    Rect { top_left: Point, bottom_right: Point },
+    // The points at most `radius` far from the center of the table, which lies on a half-integer
+    // coordinate for an even number of rows or columns, hence doubled
+    Circle { doubled_center: Point, radius: usize },
+}
+
+impl Bounds {
+    pub fn of_table(table: &Table) -> Self {
+        let (rows, cols) = (isize::try_from(table.rows()).unwrap(), isize::try_from(table.cols()).unwrap());
+        Bounds::Rect { top_left: (0, 0), bottom_right: (rows - 1, cols - 1) }
+    }
+
+    pub fn around_table(table: &Table, radius: usize) -> Self {
+        let (rows, cols) = (isize::try_from(table.rows()).unwrap(), isize::try_from(table.cols()).unwrap());
+        Bounds::Circle { doubled_center: (rows - 1, cols - 1), radius }
+    }
+
+    // Enough for convex bounds
+    pub fn contains_table(&self, table: &Table) -> bool {
+        let Bounds::Rect { bottom_right: (last_r, last_c), .. } = Bounds::of_table(table) else {
+            unreachable!();
+        };
+        [(0, 0), (0, last_c), (last_r, 0), (last_r, last_c)].into_iter().all(|corner| self.contains(corner))
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        match *self {
+            Bounds::Rect { top_left, bottom_right } => {
+                (top_left.0..=bottom_right.0).contains(&point.0) && (top_left.1..=bottom_right.1).contains(&point.1)
+            }
+            Bounds::Circle { doubled_center, radius } => {
+                let (dr, dc) = (2 * point.0 - doubled_center.0, 2 * point.1 - doubled_center.1);
+                let doubled_radius = 2 * isize::try_from(radius).unwrap();
+                dr * dr + dc * dc <= doubled_radius * doubled_radius
+            }
+        }
+    }
+}
+
+pub fn to_point(pos: Pos) -> Point {
+    (isize::try_from(pos.r()).unwrap(), isize::try_from(pos.c()).unwrap())
+}
+
+pub fn to_pos(point: Point) -> Pos {
+    Pos::new(point.0.try_into().unwrap(), point.1.try_into().unwrap())
+}
@@ -2,86 +2,318 @@
 //
 // SPDX-License-Identifier: MIT
 
-use std::{collections::HashMap, io};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env, fs, io,
+};
 
+use antennas::{AntennaClass, Legend};
+use antinodes::AntinodeSet;
+use bounds::{Bounds, Point};
 use itertools::Itertools;
+use rules::{AntinodeRule, Placement, Region, Stepping};
+use sources::{find_antinode_sources, most_productive_freqs};
 use utils::{Pos, Table};
 
+mod antennas;
+mod antinodes;
+mod bounds;
+mod rules;
+mod sources;
+
 fn main() {
-    let input = Table::from_reader(io::stdin().lock());
-    // let res = solve_part1(input);
-    let res = solve_part2(input);
-    println!("{res}");
-}
+    // [--part 1|2] [--stepping aoc|exact] [--max-harmonics <n>] [--ratios <p>:<q>,...]
+    //     [--region anywhere|between|outside] [--cross-frequency], where the options after
+    //     `--part` change the rule of the part
+    // [--rect <r0>,<c0>,<r1>,<c1> | --radius <n>] looks for antinodes in a rectangle (with signed
+    //     corners) or a circle around the center of the map, either of which must contain the map
+    // [--antennas alnum|any | --antenna-chars <chars>] sets which characters are antennas,
+    //     [--legend <file>] names the frequencies (see `Legend`)
+    // Modes: none (count the antinodes), `both` (the answers of both parts), `map` (the input
+    //     with the antinodes marked as `#`) or `sources` (list the antinodes with the antenna pairs
+    //     producing them, followed by the antinode counts per frequency). Only none and `list`
+    //     (the antinode coordinates) are supported with `--rect` or `--radius`.
+    let mut part = 2;
+    let mut stepping = Stepping::AocCompatible;
+    let mut max_harmonics = None;
+    let mut ratios = None;
+    let mut region = None;
+    let mut cross_frequency = false;
+    let mut rect = None;
+    let mut radius = None;
+    let mut antennas = AntennaClass::default();
+    let mut legend = Legend::default();
+    let mut mode = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut option_value = || args.next().unwrap_or_else(|| panic!("missing value of {arg}"));
+        match arg.as_str() {
+            "--part" => part = option_value().parse().unwrap(),
+            "--stepping" => {
+                let name = option_value();
+                stepping = Stepping::from_name(&name).unwrap_or_else(|| panic!("unknown stepping {name:?}"));
+            }
+            "--max-harmonics" => max_harmonics = Some(option_value().parse().unwrap()),
+            "--ratios" => ratios = Some(option_value().split(',').map(parse_ratio).collect()),
+            "--region" => {
+                let name = option_value();
+                region = Some(Region::from_name(&name).unwrap_or_else(|| panic!("unknown region {name:?}")));
+            }
+            "--cross-frequency" => cross_frequency = true,
+            "--rect" => rect = Some(parse_rect(&option_value())),
+            "--radius" => radius = Some(option_value().parse().unwrap()),
+            "--antennas" => {
+                let name = option_value();
+                antennas = AntennaClass::from_name(&name).unwrap_or_else(|| panic!("unknown antenna class {name:?}"));
+            }
+            "--antenna-chars" => antennas = AntennaClass::Set(option_value().into_bytes()),
+            "--legend" => legend = Legend::from_bytes(&fs::read(option_value()).unwrap()),
+            _ if arg.starts_with("--") => panic!("unknown option {arg:?}"),
+            _ if mode.is_none() => mode = Some(arg),
+            _ => panic!("unexpected argument {arg:?}"),
+        }
+    }
+    let mut rule = match part {
+        1 => AntinodeRule::part1(),
+        2 => AntinodeRule::part2(stepping),
+        _ => panic!("unknown part {part}, expected 1 or 2"),
+    };
+    if let Some(ratios) = ratios {
+        rule.placement = Placement::Ratios(ratios);
+    } else if max_harmonics.is_some() {
+        rule.placement = Placement::Collinear { stepping, max_harmonics };
+    }
+    if let Some(region) = region {
+        rule.region = region;
+    }
+    rule.cross_frequency |= cross_frequency;
 
-fn solve_part1(mut table: Table) -> usize {
-    let antenna_positions_by_freq = get_antenna_positions_by_freq(&table);
-
-    let mut num_antinodes = 0;
-    for (&_freq, antenna_positions) in &antenna_positions_by_freq {
-        for (a, b) in antenna_positions.iter().tuple_combinations() {
-            let diff = b.diff(&a);
-            if let Some(ab_antinode) = table.move_from_pos(*b, diff, 1) {
-                if table[ab_antinode] != b'#' {
-                    table[ab_antinode] = b'#';
-                    num_antinodes += 1;
+    let input = Table::from_reader(io::stdin().lock());
+    let bounds = match (rect, radius) {
+        (Some((top_left, bottom_right)), None) => Some(Bounds::Rect { top_left, bottom_right }),
+        (None, Some(radius)) => Some(Bounds::around_table(&input, radius)),
+        (None, None) => None,
+        (Some(_), Some(_)) => panic!("--rect and --radius can't be combined"),
+    };
+    if let Some(bounds) = bounds {
+        assert!(bounds.contains_table(&input), "{bounds:?} don't contain the whole map");
+        let antinodes = find_antinodes_within(&input, &antennas, &rule, &bounds);
+        match mode.as_deref() {
+            None => println!("{}", antinodes.len()),
+            Some("list") => {
+                for (r, c) in antinodes {
+                    println!("{r},{c}");
                 }
             }
-            if let Some(ba_antinode) = table.move_from_pos(*a, diff, -1) {
-                if table[ba_antinode] != b'#' {
-                    table[ba_antinode] = b'#';
-                    num_antinodes += 1;
-                }
+            Some(mode) => panic!("unknown mode {mode:?} with bounds, expected \"list\""),
+        }
+        return;
+    }
+    match mode.as_deref() {
+        None => {
+            let res = solve(&input, &antennas, &rule);
+            println!("{res}");
+        }
+        Some("both") => {
+            println!("{}", solve(&input, &antennas, &AntinodeRule::part1()));
+            println!("{}", solve(&input, &antennas, &AntinodeRule::part2(stepping)));
+        }
+        Some("map") => {
+            let overlaid = find_antinodes(&input, &antennas, &rule).overlay(&input, &antennas);
+            for row in overlaid.all_positions().map(|pos| overlaid[pos]).collect::<Vec<_>>().chunks(overlaid.cols()) {
+                println!("{}", String::from_utf8_lossy(row));
+            }
+        }
+        Some("sources") => {
+            let sources = find_antinode_sources(&input, &antennas, &rule);
+            for (pos, antinode_sources) in &sources {
+                let antinode_sources: Vec<String> = antinode_sources.iter().map(|source| source.format(&legend)).collect();
+                println!("{},{}: {}", pos.r(), pos.c(), antinode_sources.join(", "));
+            }
+            println!();
+            for (freq, count) in most_productive_freqs(&sources) {
+                println!("{}: {count}", legend.label(freq));
             }
         }
+        Some(mode) => panic!("unknown mode {mode:?}, expected \"both\", \"map\" or \"sources\""),
     }
+}
 
-    num_antinodes
+fn parse_ratio(s: &str) -> (u32, u32) {
+    let (p, q) = s.split_once(':').unwrap_or_else(|| panic!("ratio {s:?} isn't in the form <p>:<q>"));
+    let (p, q) = (p.parse().unwrap(), q.parse().unwrap());
+    assert!(p > 0 && q > 0, "ratio {s:?} must be positive");
+    (p, q)
 }
 
-fn solve_part2(mut table: Table) -> usize {
-    let antenna_positions_by_freq = get_antenna_positions_by_freq(&table);
-
-    let mut num_antinodes = 0;
-    for (&_freq, antenna_positions) in &antenna_positions_by_freq {
-        for (a, b) in antenna_positions.iter().tuple_combinations() {
-            let diff = b.diff(&a);
-            for i in 0.. {
-                let Some(ab_antinode) = table.move_from_pos(*a, diff, i) else {
-                    break;
-                };
-                if table[ab_antinode] != b'#' {
-                    table[ab_antinode] = b'#';
-                    num_antinodes += 1;
-                }
-            }
-            for i in (1..).map(|n| -n) {
-                let Some(ba_antinode) = table.move_from_pos(*a, diff, i) else {
-                    break;
-                };
-                if table[ba_antinode] != b'#' {
-                    table[ba_antinode] = b'#';
-                    num_antinodes += 1;
-                }
-            }
+fn parse_rect(s: &str) -> (Point, Point) {
+    let coords: Vec<isize> = s.split(',').map(|coord| coord.parse().unwrap()).collect();
+    let &[r0, c0, r1, c1] = coords.as_slice() else {
+        panic!("rectangle {s:?} isn't in the form <r0>,<c0>,<r1>,<c1>");
+    };
+    ((r0.min(r1), c0.min(c1)), (r0.max(r1), c0.max(c1)))
+}
+
+fn solve(table: &Table, antennas: &AntennaClass, rule: &AntinodeRule) -> usize {
+    find_antinodes(table, antennas, rule).len()
+}
+
+fn find_antinodes(table: &Table, antennas: &AntennaClass, rule: &AntinodeRule) -> AntinodeSet {
+    let mut antinodes = AntinodeSet::new(table);
+    for (a, b) in antenna_pairs(table, antennas, rule) {
+        for antinode in rule.antinodes(table, a, b) {
+            antinodes.insert(antinode);
         }
     }
+    antinodes
+}
+
+fn find_antinodes_within(table: &Table, antennas: &AntennaClass, rule: &AntinodeRule, bounds: &Bounds) -> BTreeSet<Point> {
+    antenna_pairs(table, antennas, rule)
+        .into_iter()
+        .flat_map(|(a, b)| rule.antinodes_within(bounds, bounds::to_point(a), bounds::to_point(b)))
+        .collect()
+}
 
-    num_antinodes
+// The antenna pairs that create antinodes under the rule
+fn antenna_pairs(table: &Table, antennas: &AntennaClass, rule: &AntinodeRule) -> Vec<(Pos, Pos)> {
+    let antenna_positions_by_freq = get_antenna_positions_by_freq(table, antennas);
+    let antenna_groups: Vec<Vec<Pos>> = if rule.cross_frequency {
+        vec![antenna_positions_by_freq.into_values().flatten().collect()]
+    } else {
+        antenna_positions_by_freq.into_values().collect()
+    };
+    antenna_groups
+        .iter()
+        .flat_map(|antenna_positions| antenna_positions.iter().copied().tuple_combinations())
+        .collect()
 }
 
-fn get_antenna_positions_by_freq(table: &Table) -> HashMap<u8, Vec<Pos>> {
-    let mut res: HashMap<u8, Vec<Pos>> = HashMap::new();
+fn get_antenna_positions_by_freq(table: &Table, antennas: &AntennaClass) -> BTreeMap<u8, Vec<Pos>> {
+    let mut res: BTreeMap<u8, Vec<Pos>> = BTreeMap::new();
     let it = table
         .all_positions()
         .map(|pos| (pos, table[pos]))
-        .filter(|&(_, c)| is_antenna(c));
+        .filter(|&(_, c)| antennas.is_antenna(c));
     for (pos, freq) in it {
         res.entry(freq).or_default().push(pos);
     }
     res
 }
 
-fn is_antenna(c: u8) -> bool {
-    c.is_ascii_lowercase() || c.is_ascii_uppercase() || c.is_ascii_digit()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALPHANUMERIC: AntennaClass = AntennaClass::Alphanumeric;
+
+    const EXAMPLE: &[u8] = b"\
+............
+........0...
+.....0......
+.......0....
+....0.......
+......A.....
+............
+............
+........A...
+.........A..
+............
+............
+";
+
+    #[test]
+    fn example() {
+        let table = Table::from_reader(EXAMPLE);
+        assert_eq!(solve(&table, &ALPHANUMERIC, &AntinodeRule::part1()), 14);
+        assert_eq!(solve(&table, &ALPHANUMERIC, &AntinodeRule::part2(Stepping::AocCompatible)), 34);
+        // No antenna pair of the example has a difference with a common factor
+        assert_eq!(solve(&table, &ALPHANUMERIC, &AntinodeRule::part2(Stepping::Exact)), 34);
+    }
+
+    #[test]
+    fn stepping() {
+        let table = Table::new(5, b"\
+a....\
+.....\
+....a".to_vec());
+        assert_eq!(solve(&table, &ALPHANUMERIC, &AntinodeRule::part2(Stepping::AocCompatible)), 2);
+        assert_eq!(solve(&table, &ALPHANUMERIC, &AntinodeRule::part2(Stepping::Exact)), 3);
+    }
+
+    #[test]
+    fn cross_frequency() {
+        let table = Table::new(7, b"..a.b..".to_vec());
+        let mut rule = AntinodeRule::part1();
+        assert_eq!(solve(&table, &ALPHANUMERIC, &rule), 0);
+        rule.cross_frequency = true;
+        assert_eq!(solve(&table, &ALPHANUMERIC, &rule), 2);
+    }
+
+    #[test]
+    fn sources() {
+        let table = Table::from_reader(EXAMPLE);
+        let rule = AntinodeRule::part1();
+        let sources = find_antinode_sources(&table, &ALPHANUMERIC, &rule);
+        assert_eq!(sources.len(), solve(&table, &ALPHANUMERIC, &rule));
+        let formatted: Vec<String> = sources
+            .iter()
+            .filter(|(_, antinode_sources)| antinode_sources.len() > 1)
+            .map(|(pos, antinode_sources)| {
+                let antinode_sources: Vec<String> = antinode_sources.iter().map(|source| source.format(&Legend::default())).collect();
+                format!("{},{}: {}", pos.r(), pos.c(), antinode_sources.join(", "))
+            })
+            .collect();
+        // The only antinode produced by more than one pair, counted for both frequencies
+        assert_eq!(formatted, ["1,3: 0 (2,5)-(3,7), A (5,6)-(9,9)"]);
+        assert_eq!(most_productive_freqs(&sources), [(b'0', 10), (b'A', 5)]);
+    }
+
+    #[test]
+    fn bounds() {
+        let table = Table::from_reader(EXAMPLE);
+        let rule = AntinodeRule::part1();
+        // The map itself
+        assert_eq!(find_antinodes_within(&table, &ALPHANUMERIC, &rule, &Bounds::of_table(&table)).len(), 14);
+        // The antinodes that fall off the map
+        let rect = Bounds::Rect { top_left: (-2, 0), bottom_right: (13, 12) };
+        let outside: Vec<Point> = find_antinodes_within(&table, &ALPHANUMERIC, &rule, &rect)
+            .into_iter()
+            .filter(|&(r, c)| !(0..12).contains(&r) || !(0..12).contains(&c))
+            .collect();
+        assert_eq!(outside, [(-2, 12), (-1, 9), (13, 12)]);
+        assert!(!Bounds::around_table(&table, 7).contains_table(&table));
+        assert!(Bounds::around_table(&table, 8).contains_table(&table));
+        // The part 2 antinodes form lines, so their number grows roughly linearly with the radius
+        let rule = AntinodeRule::part2(Stepping::AocCompatible);
+        let counts: Vec<usize> = [8, 16, 32]
+            .into_iter()
+            .map(|radius| find_antinodes_within(&table, &ALPHANUMERIC, &rule, &Bounds::around_table(&table, radius)).len())
+            .collect();
+        assert_eq!(counts, [40, 92, 200]);
+    }
+
+    #[test]
+    fn antenna_class() {
+        let table = Table::new(7, b"..@.@..".to_vec());
+        assert_eq!(solve(&table, &ALPHANUMERIC, &AntinodeRule::part1()), 0);
+        assert_eq!(solve(&table, &AntennaClass::Any, &AntinodeRule::part1()), 2);
+        assert_eq!(solve(&table, &AntennaClass::Set(b"@".to_vec()), &AntinodeRule::part1()), 2);
+        let legend = Legend::from_bytes(b"@ 5GHz\n");
+        let sources = find_antinode_sources(&table, &AntennaClass::Any, &AntinodeRule::part1());
+        assert_eq!(sources[0].1[0].format(&legend), "5GHz (0,2)-(0,4)");
+        // The `\r`s of CRLF line endings end up in the last column, but aren't antennas
+        let table = Table::from_reader(&b"..@.@..\r\n.......\r\n.......\r\n.......\r\n"[..]);
+        assert_eq!(solve(&table, &AntennaClass::Any, &AntinodeRule::part1()), 2);
+    }
+
+    #[test]
+    fn overlay() {
+        let table = Table::from_reader(EXAMPLE);
+        let overlaid = find_antinodes(&table, &ALPHANUMERIC, &AntinodeRule::part1()).overlay(&table, &ALPHANUMERIC);
+        let row = |table: &Table, r| (0..table.cols()).map(|c| char::from(table[Pos::new(r, c)])).collect::<String>();
+        // The antinode at the `A` antenna keeps the antenna visible
+        assert_eq!(row(&overlaid, 5), ".#....A.....");
+        assert_eq!(row(&table, 5), "......A.....");
+    }
 }
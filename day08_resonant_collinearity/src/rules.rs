@@ -0,0 +1,197 @@
+// SPDX-FileCopyrightText: 2024 Petr Pucil <petr.pucil@seznam.cz>
+//
+// SPDX-License-Identifier: MIT
+
+use utils::{Pos, Table};
+
+use crate::bounds::{to_point, to_pos, Bounds, Point};
+
+// Where a pair of antennas creates antinodes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AntinodeRule {
+    pub placement: Placement,
+    pub region: Region,
+    // Antennas of different frequencies create antinodes too
+    pub cross_frequency: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Placement {
+    // The points where one antenna is `p / q` times as far as the other, for each `(p, q)`
+    Ratios(Vec<(u32, u32)>),
+    // All points on the line through the antennas, but at most `max_harmonics` steps beyond
+    // either antenna if set
+    Collinear { stepping: Stepping, max_harmonics: Option<usize> },
+}
+
+// The antennas themselves are both between and outside
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Region {
+    Anywhere,
+    Between,
+    Outside,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stepping {
+    // Steps by the whole difference between the antennas, as the puzzle answers expect
+    AocCompatible,
+    // Steps by the difference divided by the gcd of its components, so that all grid points on
+    // the line are visited (e.g. (1, 2) between antennas with difference (2, 4))
+    Exact,
+}
+
+impl AntinodeRule {
+    pub fn part1() -> Self {
+        Self { placement: Placement::Ratios(vec![(2, 1)]), region: Region::Outside, cross_frequency: false }
+    }
+
+    pub fn part2(stepping: Stepping) -> Self {
+        Self {
+            placement: Placement::Collinear { stepping, max_harmonics: None },
+            region: Region::Anywhere,
+            cross_frequency: false,
+        }
+    }
+
+    // The antinodes of antennas `a` and `b` within the table, possibly with duplicates
+    pub fn antinodes(&self, table: &Table, a: Pos, b: Pos) -> Vec<Pos> {
+        self.antinodes_within(&Bounds::of_table(table), to_point(a), to_point(b)).into_iter().map(to_pos).collect()
+    }
+
+    // Like `antinodes`, but anywhere within the bounds, not only in the table
+    pub fn antinodes_within(&self, bounds: &Bounds, a: Point, b: Point) -> Vec<Point> {
+        let diff = (b.0 - a.0, b.1 - a.1);
+        match &self.placement {
+            Placement::Ratios(ratios) => ratios
+                .iter()
+                .flat_map(|&(p, q)| ratio_fractions(p, q))
+                .filter(|&(n, d)| self.region.contains(n, d))
+                .filter(|&(n, d)| (diff.0 * n) % d == 0 && (diff.1 * n) % d == 0)
+                .map(|(n, d)| (a.0 + diff.0 * n / d, a.1 + diff.1 * n / d))
+                .filter(|&antinode| bounds.contains(antinode))
+                .collect(),
+            &Placement::Collinear { stepping, max_harmonics } => {
+                let step = stepping.step(diff);
+                // `b` is `num_steps` steps from `a`
+                let num_steps = if step.0 != 0 { diff.0 / step.0 } else { diff.1 / step.1 };
+                let max_harmonics = max_harmonics.map_or(isize::MAX, |h| h.try_into().unwrap());
+                let mut antinodes = vec![];
+                // From `a` towards and past `b`, then from `a` the other way
+                for (start, dir) in [(0, 1), (-1, -1)] {
+                    for k in (0..).map(|i| start + i * dir) {
+                        let antinode = (a.0 + step.0 * k, a.1 + step.1 * k);
+                        let is_within_harmonics = -max_harmonics <= k && k - num_steps <= max_harmonics;
+                        if !is_within_harmonics || !bounds.contains(antinode) {
+                            break;
+                        }
+                        if self.region.contains(k, num_steps) {
+                            antinodes.push(antinode);
+                        }
+                    }
+                }
+                antinodes
+            }
+        }
+    }
+}
+
+// The positions `a + (b - a) * n / d` (as `(n, d)` with `d > 0`) where one antenna is `p / q`
+// times as far as the other
+fn ratio_fractions(p: u32, q: u32) -> Vec<(isize, isize)> {
+    let (p, q) = (isize::try_from(p).unwrap(), isize::try_from(q).unwrap());
+    [(p, p - q), (q, q - p), (p, p + q), (q, p + q)]
+        .into_iter()
+        .filter(|&(_, d)| d != 0)
+        .map(|(n, d)| if d < 0 { (-n, -d) } else { (n, d) })
+        .collect()
+}
+
+impl Region {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "anywhere" => Some(Region::Anywhere),
+            "between" => Some(Region::Between),
+            "outside" => Some(Region::Outside),
+            _ => None,
+        }
+    }
+
+    // Whether `a + (b - a) * n / d` (with `d > 0`) is in the region
+    fn contains(self, n: isize, d: isize) -> bool {
+        match self {
+            Region::Anywhere => true,
+            Region::Between => 0 <= n && n <= d,
+            Region::Outside => n <= 0 || n >= d,
+        }
+    }
+}
+
+impl Stepping {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "aoc" => Some(Stepping::AocCompatible),
+            "exact" => Some(Stepping::Exact),
+            _ => None,
+        }
+    }
+
+    fn step(self, diff: (isize, isize)) -> (isize, isize) {
+        match self {
+            Stepping::AocCompatible => diff,
+            Stepping::Exact => {
+                let divisor = gcd(diff.0.unsigned_abs(), diff.1.unsigned_abs()) as isize;
+                (diff.0 / divisor, diff.1 / divisor)
+            }
+        }
+    }
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pair_antinodes() {
+        let table = Table::new(10, vec![b'.'; 10]);
+        let (a, b) = (Pos::new(0, 3), Pos::new(0, 6));
+        let antinodes = |rule: &AntinodeRule| {
+            let mut antinodes: Vec<usize> = rule.antinodes(&table, a, b).iter().map(|pos| pos.c()).collect();
+            antinodes.sort();
+            antinodes.dedup();
+            antinodes
+        };
+        let mut rule = AntinodeRule::part1();
+        assert_eq!(antinodes(&rule), [0, 9]);
+        rule.region = Region::Anywhere;
+        assert_eq!(antinodes(&rule), [0, 4, 5, 9]);
+        rule.region = Region::Between;
+        assert_eq!(antinodes(&rule), [4, 5]);
+        rule.placement = Placement::Ratios(vec![(1, 1), (2, 1)]);
+        // 4.5 isn't a grid point
+        assert_eq!(antinodes(&rule), [4, 5]);
+        rule.placement = Placement::Ratios(vec![(4, 1)]);
+        rule.region = Region::Anywhere;
+        assert_eq!(antinodes(&rule), [2, 7]);
+
+        let mut rule = AntinodeRule::part2(Stepping::Exact);
+        assert_eq!(antinodes(&rule), (0..10).collect::<Vec<_>>());
+        rule.placement = Placement::Collinear { stepping: Stepping::Exact, max_harmonics: Some(2) };
+        assert_eq!(antinodes(&rule), [1, 2, 3, 4, 5, 6, 7, 8]);
+        rule.region = Region::Outside;
+        assert_eq!(antinodes(&rule), [1, 2, 3, 6, 7, 8]);
+        rule.placement = Placement::Collinear { stepping: Stepping::AocCompatible, max_harmonics: Some(1) };
+        rule.region = Region::Anywhere;
+        assert_eq!(antinodes(&rule), [0, 3, 6, 9]);
+        assert_eq!(Stepping::Exact.step((-4, 6)), (-2, 3));
+        assert_eq!(Stepping::Exact.step((0, -3)), (0, -1));
+    }
+}
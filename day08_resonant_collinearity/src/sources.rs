@@ -0,0 +1,67 @@
+// SPDX-FileCopyrightText: 2024 Petr Pucil <petr.pucil@seznam.cz>
+//
+// SPDX-License-Identifier: MIT
+
+use std::collections::HashMap;
+
+use utils::{Pos, Table};
+
+use crate::{
+    antenna_pairs,
+    antennas::{AntennaClass, Legend},
+    rules::AntinodeRule,
+};
+
+// An antenna pair that produced an antinode, the frequencies differ only for cross-frequency rules
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Source {
+    pub antennas: (Pos, Pos),
+    pub freqs: (u8, u8),
+}
+
+impl Source {
+    pub fn format(&self, legend: &Legend) -> String {
+        let (a, b) = self.antennas;
+        let freqs = if self.freqs.0 == self.freqs.1 {
+            legend.label(self.freqs.0)
+        } else {
+            format!("{}/{}", legend.label(self.freqs.0), legend.label(self.freqs.1))
+        };
+        format!("{freqs} ({},{})-({},{})", a.r(), a.c(), b.r(), b.c())
+    }
+}
+
+// All antinodes and the antenna pairs producing each of them, ordered by position
+pub fn find_antinode_sources(table: &Table, antennas: &AntennaClass, rule: &AntinodeRule) -> Vec<(Pos, Vec<Source>)> {
+    let mut sources: HashMap<Pos, Vec<Source>> = HashMap::new();
+    for (a, b) in antenna_pairs(table, antennas, rule) {
+        let source = Source { antennas: (a, b), freqs: (table[a], table[b]) };
+        for antinode in rule.antinodes(table, a, b) {
+            let antinode_sources = sources.entry(antinode).or_default();
+            // The same pair can produce an antinode more than once (e.g. the ratios 1:2 and 2:1)
+            if !antinode_sources.contains(&source) {
+                antinode_sources.push(source);
+            }
+        }
+    }
+    let mut sources: Vec<_> = sources.into_iter().collect();
+    sources.sort_by_key(|&(pos, _)| (pos.r(), pos.c()));
+    sources
+}
+
+// The number of antinodes produced by each frequency, the most productive first. An antinode counts
+// once for every frequency producing it, so the counts may add up to more than the antinodes.
+pub fn most_productive_freqs(sources: &[(Pos, Vec<Source>)]) -> Vec<(u8, usize)> {
+    let mut counts: HashMap<u8, usize> = HashMap::new();
+    for (_, antinode_sources) in sources {
+        let mut freqs: Vec<u8> = antinode_sources.iter().flat_map(|s| [s.freqs.0, s.freqs.1]).collect();
+        freqs.sort();
+        freqs.dedup();
+        for freq in freqs {
+            *counts.entry(freq).or_default() += 1;
+        }
+    }
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by_key(|&(freq, count)| (std::cmp::Reverse(count), freq));
+    counts
+}
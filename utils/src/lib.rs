@@ -12,6 +12,18 @@ pub struct Pos {
 }
 
 impl Pos {
+    pub fn new(r: usize, c: usize) -> Self {
+        Self { r, c }
+    }
+
+    pub fn r(&self) -> usize {
+        self.r
+    }
+
+    pub fn c(&self) -> usize {
+        self.c
+    }
+
     pub fn diff(&self, other: &Self) -> (isize, isize) {
         (
             isize::try_from(self.r).unwrap() - isize::try_from(other.r).unwrap(),
@@ -63,6 +75,14 @@ impl Table {
         Self::new(cols, cells)
     }
 
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
     pub fn all_positions(&self) -> impl Iterator<Item = Pos> + use<'_> {
         // https://stackoverflow.com/q/53722749/12940655
         (0..self.rows).flat_map(|r| (0..self.cols).map(move |c| Pos { r, c }))